@@ -0,0 +1,174 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::error::{GuruError, GuruErrorCode};
+
+/// How long a cached `listKnowledgeBases`/`getKnowledgeBaseInfo` response is
+/// trusted before it's re-fetched, on top of the explicit invalidation done
+/// by `invalidate_kb_cache` after a mutation command
+const KB_CACHE_TTL_SECS: u64 = 60;
+
+/// Local, embedded cache of analysis results and knowledge-base metadata, so
+/// re-running an unchanged file through `analyze_files_manual`/
+/// `analyze_filesystem` or re-listing KBs doesn't round-trip to the node
+/// backend. Backed by two `sled` trees in one on-disk database: analysis
+/// results never expire on their own (only `clear_all` drops them), while KB
+/// metadata is TTL-bounded and additionally invalidated by mutation commands.
+/// `analyze_files_manual` entries are keyed by file contents, so an edited
+/// file misses the cache; `analyze_filesystem` entries are keyed by the
+/// options payload only, so edits under an already-analyzed directory are
+/// not detected and the cached result is returned until `clear_all`.
+pub struct AnalysisCache {
+    db: sled::Db,
+    analysis: sled::Tree,
+    kb_metadata: sled::Tree,
+}
+
+/// Disk usage and entry counts, for the `cache_stats` command
+#[derive(Debug, Serialize)]
+pub struct CacheStats {
+    pub analysis_entries: usize,
+    pub kb_entries: usize,
+    pub disk_size_bytes: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+struct KbCacheEntry {
+    cached_at_unix_secs: u64,
+    value: Value,
+}
+
+fn sled_err(e: sled::Error) -> GuruError {
+    GuruError::new(GuruErrorCode::FileIo, format!("Analysis cache error: {e}"))
+}
+
+fn now_unix_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+impl AnalysisCache {
+    pub fn open(path: &Path) -> Result<Self, GuruError> {
+        let db = sled::open(path).map_err(|e| {
+            GuruError::new(
+                GuruErrorCode::FileIo,
+                format!("Failed to open analysis cache at {}: {e}", path.display()),
+            )
+        })?;
+        let analysis = db.open_tree("analysis").map_err(sled_err)?;
+        let kb_metadata = db.open_tree("kb_metadata").map_err(sled_err)?;
+
+        Ok(Self {
+            db,
+            analysis,
+            kb_metadata,
+        })
+    }
+
+    /// Open the cache at `path`, falling back to an unpersisted in-memory
+    /// database (cleaned up on drop) if that fails, so a cache problem
+    /// (disk full, directory locked by another running instance, ...)
+    /// degrades to "no caching" instead of preventing the app from starting.
+    pub fn open_or_fallback(path: &Path) -> Self {
+        match Self::open(path) {
+            Ok(cache) => cache,
+            Err(e) => {
+                log::warn!("Falling back to an in-memory analysis cache: {e}");
+                Self::open_temporary().expect("Failed to open even a temporary analysis cache")
+            }
+        }
+    }
+
+    fn open_temporary() -> Result<Self, GuruError> {
+        let db = sled::Config::new().temporary(true).open().map_err(sled_err)?;
+        let analysis = db.open_tree("analysis").map_err(sled_err)?;
+        let kb_metadata = db.open_tree("kb_metadata").map_err(sled_err)?;
+        Ok(Self {
+            db,
+            analysis,
+            kb_metadata,
+        })
+    }
+
+    /// Content-hash key for a manual multi-file analysis: SHA-256 over each
+    /// file's bytes (in the given order) plus the analysis mode, so renaming
+    /// an unchanged file still hits the cache but editing one doesn't.
+    pub fn analysis_key_for_files(file_paths: &[String], analysis_mode: &str) -> Result<String, GuruError> {
+        let mut hasher = Sha256::new();
+        for path in file_paths {
+            let bytes = std::fs::read(path).map_err(|e| {
+                GuruError::new(GuruErrorCode::FileIo, format!("Failed to read {path} for cache key: {e}"))
+            })?;
+            hasher.update(&bytes);
+        }
+        hasher.update(analysis_mode.as_bytes());
+        Ok(format!("{:x}", hasher.finalize()))
+    }
+
+    /// Key for an `analyze_filesystem` call: SHA-256 over its options payload
+    /// (the directory path, mode, and any scan options). The directory's
+    /// files are not read, so the key does not change when they do.
+    pub fn analysis_key_for_value(options: &Value) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(options.to_string().as_bytes());
+        format!("{:x}", hasher.finalize())
+    }
+
+    pub fn get_analysis(&self, key: &str) -> Option<Value> {
+        let bytes = self.analysis.get(key).ok().flatten()?;
+        serde_json::from_slice(&bytes).ok()
+    }
+
+    pub fn put_analysis(&self, key: &str, value: &Value) -> Result<(), GuruError> {
+        let bytes = serde_json::to_vec(value)?;
+        self.analysis.insert(key, bytes).map_err(sled_err)?;
+        Ok(())
+    }
+
+    pub fn get_kb(&self, key: &str) -> Option<Value> {
+        let bytes = self.kb_metadata.get(key).ok().flatten()?;
+        let entry: KbCacheEntry = serde_json::from_slice(&bytes).ok()?;
+
+        if now_unix_secs().saturating_sub(entry.cached_at_unix_secs) > KB_CACHE_TTL_SECS {
+            return None;
+        }
+        Some(entry.value)
+    }
+
+    pub fn put_kb(&self, key: &str, value: &Value) -> Result<(), GuruError> {
+        let entry = KbCacheEntry {
+            cached_at_unix_secs: now_unix_secs(),
+            value: value.clone(),
+        };
+        let bytes = serde_json::to_vec(&entry)?;
+        self.kb_metadata.insert(key, bytes).map_err(sled_err)?;
+        Ok(())
+    }
+
+    /// Drop every cached KB metadata entry. Called after a mutation command
+    /// (`add_documents_to_knowledge_base`, `delete_document_from_kb`, ...)
+    /// since the cache has no cheap way to target just the affected KB's
+    /// entries.
+    pub fn invalidate_kb_cache(&self) -> Result<(), GuruError> {
+        self.kb_metadata.clear().map_err(sled_err)
+    }
+
+    pub fn clear_all(&self) -> Result<(), GuruError> {
+        self.analysis.clear().map_err(sled_err)?;
+        self.kb_metadata.clear().map_err(sled_err)?;
+        Ok(())
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            analysis_entries: self.analysis.len(),
+            kb_entries: self.kb_metadata.len(),
+            disk_size_bytes: self.db.size_on_disk().unwrap_or(0),
+        }
+    }
+}
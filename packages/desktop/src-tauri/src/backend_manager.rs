@@ -0,0 +1,421 @@
+use futures_util::{SinkExt, StreamExt};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tauri::Emitter;
+use tokio::sync::{mpsc, oneshot, Semaphore};
+use tokio_tungstenite::connect_async;
+use tokio_tungstenite::tungstenite::Message;
+
+/// Maximum number of requests allowed to be in flight on one connection at once
+const MAX_IN_FLIGHT: usize = 8;
+/// Initial delay before the first reconnect attempt, doubled after each
+/// failed attempt up to `MAX_RECONNECT_DELAY`
+const INITIAL_RECONNECT_DELAY: Duration = Duration::from_millis(500);
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(30);
+
+type PendingMap = Arc<Mutex<HashMap<u64, oneshot::Sender<Result<Value, String>>>>>;
+/// Ids of in-flight streaming requests (e.g. `analyze_files`); each
+/// `"partial": true` frame for an id is forwarded to its channel until the
+/// final, unmarked frame resolves the request via `PendingMap` instead
+type StreamingMap = Arc<Mutex<HashMap<u64, mpsc::UnboundedSender<Value>>>>;
+
+/// Connection-state transition emitted to the frontend so the UI can show
+/// live service status instead of only learning about a failure once a
+/// request times out
+#[derive(Debug, Clone, serde::Serialize)]
+struct ConnectionStateEvent {
+    service: String,
+    state: String,
+}
+
+/// A server-initiated frame carrying no request `id`, forwarded to the
+/// frontend as-is rather than resolving a pending request
+#[derive(Debug, Clone, serde::Serialize)]
+struct BackendPushEvent {
+    service: String,
+    payload: Value,
+}
+
+/// One incremental frame of a streaming request (e.g. `analyze_files` over
+/// a large batch), emitted as the backend produces partial results instead
+/// of only after the whole batch completes
+#[derive(Debug, Clone, serde::Serialize)]
+struct AnalysisProgressEvent {
+    service: String,
+    request_id: u64,
+    payload: Value,
+}
+
+/// How an incoming frame should be routed, determined purely from its
+/// shape — `run_connection` performs the actual side effects (resolving a
+/// `pending`/`streaming` sender, re-emitting a push event) for each case.
+/// See `classify_frame`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FrameKind {
+    /// No `id`: a server-initiated push, forwarded to the frontend as-is
+    Push,
+    /// `id` with `"partial": true`: an incremental frame for an in-flight
+    /// streaming request, forwarded until the final frame arrives
+    Partial(u64),
+    /// `id` without `"partial": true`: the final frame for a request,
+    /// resolving its pending sender
+    Final(u64),
+}
+
+/// Classify an incoming frame by its `id`/`partial` fields — see `FrameKind`
+fn classify_frame(frame: &Value) -> FrameKind {
+    match frame.get("id").and_then(|v| v.as_u64()) {
+        Some(id) => {
+            let is_partial = frame.get("partial").and_then(|v| v.as_bool()).unwrap_or(false);
+            if is_partial {
+                FrameKind::Partial(id)
+            } else {
+                FrameKind::Final(id)
+            }
+        }
+        None => FrameKind::Push,
+    }
+}
+
+/// Delay before the next reconnect attempt after the one that just took
+/// `prev_delay`: doubled, capped at `MAX_RECONNECT_DELAY`
+fn next_reconnect_delay(prev_delay: Duration) -> Duration {
+    (prev_delay * 2).min(MAX_RECONNECT_DELAY)
+}
+
+/// Owns a resilient WebSocket connection to a Guru backend endpoint,
+/// replacing the previous one-shot `TcpStream` per call. A background task
+/// (started by `start`) maintains the connection: it connects, hands
+/// requests routing off to a reader/writer pair, and on any drop or error
+/// reconnects with exponential backoff, emitting `backend-connection-state`
+/// events for each transition.
+///
+/// Requests are still correlated by a monotonically increasing `id`, same as
+/// before; frames with no `id` at all are treated as server-initiated pushes
+/// and re-emitted as `backend-push` events, while a frame whose `id` matches
+/// no pending request is dropped. A request sent via `send_analyze` may additionally
+/// receive any number of `"partial": true` frames before its final one; each
+/// is re-emitted as an `analysis-progress` event rather than held until the
+/// final frame arrives.
+pub struct BackendManager {
+    addr: String,
+    label: &'static str,
+    write_tx: Mutex<Option<mpsc::UnboundedSender<Message>>>,
+    pending: PendingMap,
+    streaming: StreamingMap,
+    next_id: AtomicU64,
+    in_flight: Arc<Semaphore>,
+    connected: Arc<AtomicBool>,
+}
+
+impl BackendManager {
+    /// `label` identifies this manager in emitted events (e.g. `"backend"`
+    /// or `"mcp"`) since `AppState` holds one per endpoint
+    pub fn new(addr: impl Into<String>, label: &'static str) -> Self {
+        Self {
+            addr: addr.into(),
+            label,
+            write_tx: Mutex::new(None),
+            pending: Arc::new(Mutex::new(HashMap::new())),
+            streaming: Arc::new(Mutex::new(HashMap::new())),
+            next_id: AtomicU64::new(1),
+            in_flight: Arc::new(Semaphore::new(MAX_IN_FLIGHT)),
+            connected: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    fn url(&self) -> String {
+        format!("ws://{}", self.addr)
+    }
+
+    fn emit_state(&self, app_handle: &tauri::AppHandle, state: &str) {
+        let _ = app_handle.emit(
+            "backend-connection-state",
+            ConnectionStateEvent {
+                service: self.label.to_string(),
+                state: state.to_string(),
+            },
+        );
+    }
+
+    /// Start the background connect/reconnect loop. Safe to call once per
+    /// manager; subsequent `send*` calls route through whatever connection
+    /// the loop currently holds.
+    pub fn start(self: &Arc<Self>, app_handle: tauri::AppHandle) {
+        let manager = self.clone();
+        tauri::async_runtime::spawn(async move {
+            let mut delay = INITIAL_RECONNECT_DELAY;
+
+            loop {
+                manager.emit_state(&app_handle, "connecting");
+
+                match connect_async(manager.url()).await {
+                    Ok((ws_stream, _response)) => {
+                        log::info!("Connected to {} backend at {}", manager.label, manager.addr);
+                        manager.emit_state(&app_handle, "open");
+                        delay = INITIAL_RECONNECT_DELAY;
+
+                        manager.run_connection(ws_stream, &app_handle).await;
+
+                        manager.connected.store(false, Ordering::SeqCst);
+                        *manager.write_tx.lock().unwrap() = None;
+                        manager.emit_state(&app_handle, "closed");
+                    }
+                    Err(e) => {
+                        log::warn!(
+                            "Failed to connect to {} backend at {}: {}",
+                            manager.label,
+                            manager.addr,
+                            e
+                        );
+                        manager.emit_state(&app_handle, "closed");
+                    }
+                }
+
+                // Fail every request left waiting on the connection we just lost
+                // rather than leaving callers hanging until the next attempt.
+                for (_, sender) in manager.pending.lock().unwrap().drain() {
+                    let _ = sender.send(Err("Backend connection closed".to_string()));
+                }
+                manager.streaming.lock().unwrap().clear();
+
+                tokio::time::sleep(delay).await;
+                delay = next_reconnect_delay(delay);
+            }
+        });
+    }
+
+    /// Drive one established connection until it closes or errors: forward
+    /// outgoing frames from `send`, and demultiplex incoming ones by `id`,
+    /// treating any frame without an `id` as a server-initiated push and
+    /// dropping any frame whose `id` matches no pending request.
+    async fn run_connection(
+        &self,
+        ws_stream: tokio_tungstenite::WebSocketStream<
+            tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>,
+        >,
+        app_handle: &tauri::AppHandle,
+    ) {
+        let (mut write, mut read) = ws_stream.split();
+        let (tx, mut rx) = mpsc::unbounded_channel::<Message>();
+
+        *self.write_tx.lock().unwrap() = Some(tx);
+        self.connected.store(true, Ordering::SeqCst);
+
+        let writer = async {
+            while let Some(message) = rx.recv().await {
+                if write.send(message).await.is_err() {
+                    break;
+                }
+            }
+        };
+
+        let reader = async {
+            while let Some(message) = read.next().await {
+                let Ok(Message::Text(text)) = message else {
+                    continue;
+                };
+                let Ok(frame) = serde_json::from_str::<Value>(&text) else {
+                    continue;
+                };
+
+                match classify_frame(&frame) {
+                    FrameKind::Partial(id) => {
+                        if let Some(sender) = self.streaming.lock().unwrap().get(&id) {
+                            let _ = sender.send(frame);
+                        }
+                    }
+                    FrameKind::Final(id) => {
+                        self.streaming.lock().unwrap().remove(&id);
+                        if let Some(sender) = self.pending.lock().unwrap().remove(&id) {
+                            let response = if let Some(error) = frame.get("error") {
+                                Err(error.to_string())
+                            } else {
+                                Ok(frame.get("result").cloned().unwrap_or(Value::Null))
+                            };
+                            let _ = sender.send(response);
+                        }
+                    }
+                    FrameKind::Push => {
+                        let _ = app_handle.emit(
+                            "backend-push",
+                            BackendPushEvent {
+                                service: self.label.to_string(),
+                                payload: frame,
+                            },
+                        );
+                    }
+                }
+            }
+        };
+
+        tokio::select! {
+            _ = writer => {}
+            _ = reader => {}
+        }
+    }
+
+    /// Send a framed JSON request and await its demultiplexed response. When
+    /// `app_handle` is given, any `"partial": true` frames received for this
+    /// request before its final one are re-emitted as `analysis-progress`
+    /// events instead of being held until the final frame arrives.
+    async fn send_with_progress(
+        &self,
+        mut frame: Value,
+        app_handle: Option<&tauri::AppHandle>,
+    ) -> Result<Value, String> {
+        let _permit = self
+            .in_flight
+            .acquire()
+            .await
+            .map_err(|e| format!("Backend request queue closed: {}", e))?;
+
+        if !self.connected.load(Ordering::SeqCst) {
+            return Err(format!(
+                "Not connected to {} backend at {}. Make sure the Guru service is running.",
+                self.label, self.addr
+            ));
+        }
+
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        frame["id"] = serde_json::json!(id);
+
+        let (tx, rx) = oneshot::channel();
+        self.pending.lock().unwrap().insert(id, tx);
+
+        if let Some(app_handle) = app_handle {
+            let (progress_tx, mut progress_rx) = mpsc::unbounded_channel::<Value>();
+            self.streaming.lock().unwrap().insert(id, progress_tx);
+
+            let service = self.label.to_string();
+            let app_handle = app_handle.clone();
+            // Left to run to completion rather than joined/aborted: the reader
+            // drops this id's `progress_tx` from `streaming` on the final frame,
+            // which closes the channel and lets `recv` drain whatever partial
+            // frames were already queued before returning `None`.
+            tauri::async_runtime::spawn(async move {
+                while let Some(payload) = progress_rx.recv().await {
+                    let _ = app_handle.emit(
+                        "analysis-progress",
+                        AnalysisProgressEvent {
+                            service: service.clone(),
+                            request_id: id,
+                            payload,
+                        },
+                    );
+                }
+            });
+        }
+
+        let write_tx = self.write_tx.lock().unwrap().clone();
+        let Some(write_tx) = write_tx else {
+            self.pending.lock().unwrap().remove(&id);
+            self.streaming.lock().unwrap().remove(&id);
+            return Err(format!("Not connected to {} backend", self.label));
+        };
+
+        if write_tx.send(Message::Text(frame.to_string())).is_err() {
+            self.pending.lock().unwrap().remove(&id);
+            self.streaming.lock().unwrap().remove(&id);
+            return Err(format!("Failed to send request to {} backend", self.label));
+        }
+
+        let result = rx
+            .await
+            .map_err(|_| "Backend connection closed before response".to_string());
+        self.streaming.lock().unwrap().remove(&id);
+        result?
+    }
+
+    /// Send a framed JSON request and await its demultiplexed response
+    async fn send(&self, frame: Value) -> Result<Value, String> {
+        self.send_with_progress(frame, None).await
+    }
+
+    /// Invoke a named Guru backend command, replacing the per-call
+    /// `node guru-backend-runner.cjs` spawn
+    pub async fn send_guru_command(&self, command: &str, args: Vec<Value>) -> Result<Value, String> {
+        self.send(serde_json::json!({
+            "type": "command",
+            "command": command,
+            "args": args,
+        }))
+        .await
+    }
+
+    /// Request a filesystem/batch analysis, streaming partial results to the
+    /// frontend as they arrive rather than blocking until the whole batch
+    /// finishes
+    pub async fn send_analyze(
+        &self,
+        files: Vec<String>,
+        batch_mode: bool,
+        app_handle: &tauri::AppHandle,
+    ) -> Result<Value, String> {
+        self.send_with_progress(
+            serde_json::json!({
+                "type": "analyze",
+                "files": files,
+                "batchMode": batch_mode,
+            }),
+            Some(app_handle),
+        )
+        .await
+    }
+
+    /// Invoke an MCP tool via a `tools/call` JSON-RPC request
+    pub async fn send_mcp_tool_call(&self, tool: &str, args: &Value) -> Result<Value, String> {
+        self.send(serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": "tools/call",
+            "params": {
+                "name": tool,
+                "arguments": args,
+            },
+        }))
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify_frame_with_no_id_is_push() {
+        let frame = serde_json::json!({ "event": "status", "detail": "ready" });
+        assert_eq!(classify_frame(&frame), FrameKind::Push);
+    }
+
+    #[test]
+    fn test_classify_frame_with_partial_true_is_partial() {
+        let frame = serde_json::json!({ "id": 7, "partial": true, "result": "chunk" });
+        assert_eq!(classify_frame(&frame), FrameKind::Partial(7));
+    }
+
+    #[test]
+    fn test_classify_frame_with_id_and_no_partial_is_final() {
+        let frame = serde_json::json!({ "id": 7, "result": "done" });
+        assert_eq!(classify_frame(&frame), FrameKind::Final(7));
+    }
+
+    #[test]
+    fn test_classify_frame_with_partial_false_is_final() {
+        let frame = serde_json::json!({ "id": 3, "partial": false, "result": "done" });
+        assert_eq!(classify_frame(&frame), FrameKind::Final(3));
+    }
+
+    #[test]
+    fn test_next_reconnect_delay_doubles() {
+        assert_eq!(next_reconnect_delay(Duration::from_millis(500)), Duration::from_millis(1000));
+    }
+
+    #[test]
+    fn test_next_reconnect_delay_caps_at_max() {
+        assert_eq!(next_reconnect_delay(MAX_RECONNECT_DELAY), MAX_RECONNECT_DELAY);
+        assert_eq!(next_reconnect_delay(MAX_RECONNECT_DELAY * 2), MAX_RECONNECT_DELAY);
+    }
+}
@@ -0,0 +1,115 @@
+use serde::Serialize;
+
+/// Stable classification for a `GuruError`, so the frontend can branch on
+/// failure class (e.g. "service not running" vs. "bad request") instead of
+/// string-matching a free-form message
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum GuruErrorCode {
+    /// Couldn't reach the Guru backend/MCP service over its socket
+    BackendUnavailable,
+    /// The backend process failed to spawn
+    BackendSpawnFailed,
+    /// Failed to serialize a request or deserialize a response
+    Serialization,
+    /// A response frame didn't match the expected protocol shape
+    ProtocolParse,
+    /// The MCP server returned a `tools/call` error
+    McpError,
+    /// The model file is missing or hasn't finished downloading
+    ModelMissing,
+    /// A local filesystem operation failed
+    FileIo,
+    /// An operation didn't complete within its expected time budget
+    Timeout,
+    /// Doesn't fit a more specific code
+    Unknown,
+}
+
+/// Structured error returned by every `#[tauri::command]`, serialized as
+/// `{ code, message, details }` so the frontend can react to failure classes
+/// and localize messages instead of string-matching free-form errors
+#[derive(Debug, Clone, Serialize)]
+pub struct GuruError {
+    pub code: GuruErrorCode,
+    pub message: String,
+    pub details: Option<serde_json::Value>,
+}
+
+impl GuruError {
+    pub fn new(code: GuruErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            details: None,
+        }
+    }
+
+    pub fn with_details(mut self, details: serde_json::Value) -> Self {
+        self.details = Some(details);
+        self
+    }
+}
+
+impl std::fmt::Display for GuruError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[{:?}] {}", self.code, self.message)
+    }
+}
+
+impl std::error::Error for GuruError {}
+
+impl From<std::io::Error> for GuruError {
+    fn from(err: std::io::Error) -> Self {
+        let code = match err.kind() {
+            std::io::ErrorKind::ConnectionRefused | std::io::ErrorKind::NotConnected => {
+                GuruErrorCode::BackendUnavailable
+            }
+            std::io::ErrorKind::NotFound => GuruErrorCode::ModelMissing,
+            std::io::ErrorKind::TimedOut => GuruErrorCode::Timeout,
+            _ => GuruErrorCode::FileIo,
+        };
+        GuruError::new(code, err.to_string())
+    }
+}
+
+impl From<serde_json::Error> for GuruError {
+    fn from(err: serde_json::Error) -> Self {
+        GuruError::new(GuruErrorCode::Serialization, err.to_string())
+    }
+}
+
+/// Best-effort classification of a legacy free-form error string onto a
+/// stable code. Internal plumbing (`BackendManager`, the MCP socket helpers)
+/// still surfaces `String` errors; this lets every `#[tauri::command]`
+/// boundary convert them into a `GuruError` via `?` without rewriting each
+/// call site.
+impl From<String> for GuruError {
+    fn from(message: String) -> Self {
+        let code = if message.contains("Failed to connect") || message.contains("Make sure the Guru") {
+            GuruErrorCode::BackendUnavailable
+        } else if message.contains("Failed to spawn") || message.contains("Failed to start") {
+            GuruErrorCode::BackendSpawnFailed
+        } else if message.contains("MCP error") || message.contains("Invalid MCP response") {
+            GuruErrorCode::McpError
+        } else if message.contains("Failed to parse") || message.contains("Invalid") {
+            GuruErrorCode::ProtocolParse
+        } else if message.contains("Failed to serialize") || message.contains("Failed to deserialize") {
+            GuruErrorCode::Serialization
+        } else if message.contains("model") && (message.contains("not found") || message.contains("missing")) {
+            GuruErrorCode::ModelMissing
+        } else if message.contains("Failed to read") || message.contains("Failed to write") || message.contains("Failed to create") {
+            GuruErrorCode::FileIo
+        } else {
+            GuruErrorCode::Unknown
+        };
+
+        GuruError::new(code, message)
+    }
+}
+
+impl From<&str> for GuruError {
+    fn from(message: &str) -> Self {
+        GuruError::from(message.to_string())
+    }
+}
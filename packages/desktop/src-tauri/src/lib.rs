@@ -1,10 +1,19 @@
+mod analysis_cache;
+mod backend_manager;
+mod error;
+mod model_downloader;
+
+use analysis_cache::{AnalysisCache, CacheStats};
+use backend_manager::BackendManager;
+use error::{GuruError, GuruErrorCode};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use std::collections::HashMap;
 use std::process::Command;
-use std::sync::Mutex;
+use std::sync::{Arc, Mutex};
 use tauri::{Manager, Emitter};
 
-// mod phi4_integration;
+mod phi4_integration;
 
 // Learn more about Tauri commands at https://tauri.app/develop/calling-rust/
 #[tauri::command]
@@ -42,118 +51,133 @@ struct DownloadResult {
 
 struct AppState {
     backend_process: Mutex<Option<std::process::Child>>,
+    /// Senders awaiting a frontend decision on a `may_`-prefixed tool call,
+    /// keyed by the confirmation request id handed out in `run_mcp_agent`
+    pending_confirmations: Mutex<HashMap<String, tokio::sync::oneshot::Sender<bool>>>,
+    /// Results of prior read-only tool calls in `run_mcp_agent`, keyed by
+    /// tool name + serialized args, so identical calls aren't re-run
+    mcp_tool_cache: Mutex<HashMap<String, Value>>,
+    /// Resilient WebSocket connection to the Guru backend's command/analysis
+    /// gateway, shared by `execute_guru_command` and `analyze_files`. Held as
+    /// an `Arc` so the reconnect loop spawned in `run`'s `setup` can own a
+    /// clone alongside `AppState`'s.
+    backend: Arc<BackendManager>,
+    /// Resilient WebSocket connection to the Guru MCP gateway, shared by
+    /// `execute_mcp_tool` and `run_mcp_agent`
+    mcp: Arc<BackendManager>,
+    /// Cancellation flag for an in-progress `download_model`, set by
+    /// `cancel_download`
+    download_cancel: Mutex<Option<std::sync::Arc<std::sync::atomic::AtomicBool>>>,
+    /// Embedded cache of analysis results and KB metadata, shared by
+    /// `analyze_files_manual`, `analyze_filesystem`, the KB read commands,
+    /// and the KB mutation commands that invalidate it
+    cache: Arc<AnalysisCache>,
+    /// Source of unique `pending_confirmations` keys for `run_mcp_agent`,
+    /// same pattern as `BackendManager::next_id` — a step index plus a
+    /// derived value (e.g. a key length) can collide across calls, which
+    /// would silently drop one call's confirmation sender
+    next_confirmation_id: std::sync::atomic::AtomicU64,
+}
+
+/// A single tool invocation requested by the model backend during an agent step
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct McpToolCall {
+    name: String,
+    arguments: Value,
 }
 
-// Execute Guru backend commands via Node.js
+/// Progress emitted to the frontend after each step of `run_mcp_agent`, so the
+/// UI can render the reasoning/tool trace live rather than waiting for the
+/// final answer
+#[derive(Debug, Serialize, Deserialize)]
+struct AgentStepEvent {
+    step: usize,
+    kind: String,
+    tool: Option<String>,
+    detail: Value,
+}
+
+/// Emitted when a `may_`-prefixed (side-effecting) tool call needs explicit
+/// user approval before it's dispatched
+#[derive(Debug, Serialize, Deserialize)]
+struct McpConfirmationRequest {
+    request_id: String,
+    tool: String,
+    arguments: Value,
+}
+
+// Execute Guru backend commands through the persistent BackendManager
+// connection instead of spawning a fresh `node guru-backend-runner.cjs`
+// process per call
 #[tauri::command]
-async fn execute_guru_command(command: String, args: Vec<Value>) -> Result<Value, String> {
-    use std::io::Write;
-    use std::process::{Command, Stdio};
-    
-    let args_json = serde_json::to_string(&args)
-        .map_err(|e| format!("Failed to serialize args: {}", e))?;
-    
-    // Log the command being executed
+async fn execute_guru_command(
+    state: tauri::State<'_, AppState>,
+    command: String,
+    args: Vec<Value>,
+) -> Result<Value, GuruError> {
     eprintln!("Executing guru command: {}", command);
-    
-    // Check if the args are too large for command line (> 100KB)
-    let args_size = args_json.len();
-    let use_stdin = args_size > 100_000;
-    
-    eprintln!("Args size: {} bytes, using stdin: {}", args_size, use_stdin);
-    
-    let mut cmd = Command::new("node");
-    cmd.arg("../scripts/guru-backend-runner.cjs")
-       .arg(&command);
-    
-    if use_stdin {
-        // Pass large data through stdin
-        cmd.arg("--stdin");
-        cmd.stdin(Stdio::piped());
-    } else {
-        // Pass small data as command line args
-        cmd.args(&args.iter().map(|v| v.to_string()).collect::<Vec<_>>());
-    }
-    
-    cmd.stdout(Stdio::piped())
-       .stderr(Stdio::piped());
-    
-    if use_stdin {
-        // Spawn the command and write to stdin
-        let mut child = cmd.spawn()
-            .map_err(|e| format!("Failed to spawn command: {}", e))?;
-        
-        // Write args to stdin
-        if let Some(mut stdin) = child.stdin.take() {
-            stdin.write_all(args_json.as_bytes())
-                .map_err(|e| format!("Failed to write to stdin: {}", e))?;
-        }
-        
-        // Wait for completion
-        let output = child.wait_with_output()
-            .map_err(|e| format!("Failed to wait for command: {}", e))?;
-        
-        let stdout = String::from_utf8_lossy(&output.stdout);
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        
-        eprintln!("Command stderr: {}", stderr);
-        
-        if output.status.success() {
-            serde_json::from_str(&stdout)
-                .map_err(|e| format!("Failed to parse output: {} - Raw output: {}", e, stdout))
-        } else {
-            Err(format!("Command failed: {}", stderr))
-        }
-    } else {
-        // Execute normally for small data
-        let output = cmd.output()
-            .map_err(|e| format!("Failed to execute command: {}", e))?;
-        
-        let stdout = String::from_utf8_lossy(&output.stdout);
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        
-        eprintln!("Command stderr: {}", stderr);
-        
-        if output.status.success() {
-            serde_json::from_str(&stdout)
-                .map_err(|e| format!("Failed to parse output: {} - Raw output: {}", e, stdout))
-        } else {
-            Err(format!("Command failed: {}", stderr))
-        }
-    }
+    state.backend.send_guru_command(&command, args).await.map_err(GuruError::from)
 }
 
 // File system commands
 #[tauri::command]
-async fn analyze_filesystem(options: Value) -> Result<Value, String> {
-    execute_guru_command("analyzeFilesystem".to_string(), vec![options]).await
+async fn analyze_filesystem(state: tauri::State<'_, AppState>, options: Value) -> Result<Value, GuruError> {
+    let cache_key = AnalysisCache::analysis_key_for_value(&options);
+    if let Some(cached) = state.cache.get_analysis(&cache_key) {
+        return Ok(cached);
+    }
+
+    let result = execute_guru_command(state.clone(), "analyzeFilesystem".to_string(), vec![options]).await?;
+    if let Err(e) = state.cache.put_analysis(&cache_key, &result) {
+        log::warn!("Failed to cache analysis result: {e}");
+    }
+    Ok(result)
 }
 
 #[tauri::command]
-async fn analyze_files_manual(file_paths: Vec<String>, analysis_mode: String) -> Result<Value, String> {
-    execute_guru_command(
+async fn analyze_files_manual(
+    state: tauri::State<'_, AppState>,
+    file_paths: Vec<String>,
+    analysis_mode: String,
+) -> Result<Value, GuruError> {
+    let cache_key = AnalysisCache::analysis_key_for_files(&file_paths, &analysis_mode)?;
+    if let Some(cached) = state.cache.get_analysis(&cache_key) {
+        return Ok(cached);
+    }
+
+    let result = execute_guru_command(
+        state.clone(),
         "analyzeFilesManual".to_string(),
         vec![serde_json::json!(file_paths), serde_json::json!(analysis_mode)]
-    ).await
+    ).await?;
+    if let Err(e) = state.cache.put_analysis(&cache_key, &result) {
+        log::warn!("Failed to cache analysis result: {e}");
+    }
+    Ok(result)
 }
 
 // Document commands
 #[tauri::command]
-async fn upload_documents(documents: Vec<Value>, options: Option<Value>) -> Result<Value, String> {
+async fn upload_documents(
+    state: tauri::State<'_, AppState>,
+    documents: Vec<Value>,
+    options: Option<Value>,
+) -> Result<Value, GuruError> {
     let args = match options {
         Some(opts) => vec![serde_json::json!(documents), opts],
         None => vec![serde_json::json!(documents)],
     };
-    execute_guru_command("uploadDocuments".to_string(), args).await
+    execute_guru_command(state, "uploadDocuments".to_string(), args).await
 }
 
 // Knowledge base commands
 #[tauri::command]
 async fn create_knowledge_base(
+    state: tauri::State<'_, AppState>,
     name: String,
     description: String,
     cognitive_systems_enabled: Option<Vec<String>>
-) -> Result<Value, String> {
+) -> Result<Value, GuruError> {
     let args = match cognitive_systems_enabled {
         Some(systems) => vec![
             serde_json::json!(name),
@@ -162,64 +186,110 @@ async fn create_knowledge_base(
         ],
         None => vec![serde_json::json!(name), serde_json::json!(description)],
     };
-    execute_guru_command("createKnowledgeBase".to_string(), args).await
+    let result = execute_guru_command(state.clone(), "createKnowledgeBase".to_string(), args).await?;
+    if let Err(e) = state.cache.invalidate_kb_cache() {
+        log::warn!("Failed to invalidate KB cache: {e}");
+    }
+    Ok(result)
 }
 
 #[tauri::command]
 async fn add_documents_to_knowledge_base(
+    state: tauri::State<'_, AppState>,
     kb_name: String,
     documents: Vec<Value>,
     options: Option<Value>
-) -> Result<Value, String> {
+) -> Result<Value, GuruError> {
     let args = match options {
         Some(opts) => vec![serde_json::json!(kb_name), serde_json::json!(documents), opts],
         None => vec![serde_json::json!(kb_name), serde_json::json!(documents)],
     };
-    execute_guru_command("addDocumentsToKnowledgeBase".to_string(), args).await
+    let result = execute_guru_command(state.clone(), "addDocumentsToKnowledgeBase".to_string(), args).await?;
+    if let Err(e) = state.cache.invalidate_kb_cache() {
+        log::warn!("Failed to invalidate KB cache: {e}");
+    }
+    Ok(result)
 }
 
 #[tauri::command]
 async fn query_knowledge_base(
+    state: tauri::State<'_, AppState>,
     kb_name: String,
     query: String,
     options: Option<Value>
-) -> Result<Value, String> {
+) -> Result<Value, GuruError> {
     let args = match options {
         Some(opts) => vec![serde_json::json!(kb_name), serde_json::json!(query), opts],
         None => vec![serde_json::json!(kb_name), serde_json::json!(query)],
     };
-    execute_guru_command("queryKnowledgeBase".to_string(), args).await
+    execute_guru_command(state, "queryKnowledgeBase".to_string(), args).await
 }
 
 #[tauri::command]
-async fn list_knowledge_bases() -> Result<Value, String> {
-    execute_guru_command("listKnowledgeBases".to_string(), vec![]).await
+async fn list_knowledge_bases(state: tauri::State<'_, AppState>) -> Result<Value, GuruError> {
+    const CACHE_KEY: &str = "listKnowledgeBases";
+    if let Some(cached) = state.cache.get_kb(CACHE_KEY) {
+        return Ok(cached);
+    }
+
+    let result = execute_guru_command(state.clone(), "listKnowledgeBases".to_string(), vec![]).await?;
+    if let Err(e) = state.cache.put_kb(CACHE_KEY, &result) {
+        log::warn!("Failed to cache KB list: {e}");
+    }
+    Ok(result)
 }
 
 #[tauri::command]
-async fn get_knowledge_base_info(kb_name: String) -> Result<Value, String> {
-    execute_guru_command("getKnowledgeBaseInfo".to_string(), vec![serde_json::json!(kb_name)]).await
+async fn get_knowledge_base_info(state: tauri::State<'_, AppState>, kb_name: String) -> Result<Value, GuruError> {
+    let cache_key = format!("getKnowledgeBaseInfo:{}", kb_name);
+    if let Some(cached) = state.cache.get_kb(&cache_key) {
+        return Ok(cached);
+    }
+
+    let result = execute_guru_command(state.clone(), "getKnowledgeBaseInfo".to_string(), vec![serde_json::json!(kb_name)]).await?;
+    if let Err(e) = state.cache.put_kb(&cache_key, &result) {
+        log::warn!("Failed to cache KB info: {e}");
+    }
+    Ok(result)
 }
 
 #[tauri::command]
-async fn delete_knowledge_base(kb_name: String, confirm: bool) -> Result<Value, String> {
-    execute_guru_command(
+async fn delete_knowledge_base(
+    state: tauri::State<'_, AppState>,
+    kb_name: String,
+    confirm: bool,
+) -> Result<Value, GuruError> {
+    let result = execute_guru_command(
+        state.clone(),
         "deleteKnowledgeBase".to_string(),
         vec![serde_json::json!(kb_name), serde_json::json!(confirm)]
-    ).await
+    ).await?;
+    if let Err(e) = state.cache.invalidate_kb_cache() {
+        log::warn!("Failed to invalidate KB cache: {e}");
+    }
+    Ok(result)
 }
 
 #[tauri::command]
-async fn list_documents_in_kb(kb_name: String) -> Result<Value, String> {
-    execute_guru_command("listDocumentsInKnowledgeBase".to_string(), vec![serde_json::json!(kb_name)]).await
+async fn list_documents_in_kb(state: tauri::State<'_, AppState>, kb_name: String) -> Result<Value, GuruError> {
+    execute_guru_command(state, "listDocumentsInKnowledgeBase".to_string(), vec![serde_json::json!(kb_name)]).await
 }
 
 #[tauri::command]
-async fn delete_document_from_kb(kb_name: String, document_id: String) -> Result<Value, String> {
-    execute_guru_command(
+async fn delete_document_from_kb(
+    state: tauri::State<'_, AppState>,
+    kb_name: String,
+    document_id: String,
+) -> Result<Value, GuruError> {
+    let result = execute_guru_command(
+        state.clone(),
         "deleteDocumentFromKnowledgeBase".to_string(),
         vec![serde_json::json!(kb_name), serde_json::json!(document_id)]
-    ).await
+    ).await?;
+    if let Err(e) = state.cache.invalidate_kb_cache() {
+        log::warn!("Failed to invalidate KB cache: {e}");
+    }
+    Ok(result)
 }
 
 // File dialog commands
@@ -228,7 +298,7 @@ async fn open_file_dialog(
     app: tauri::AppHandle,
     multiple: bool,
     filters: Option<Vec<(String, Vec<String>)>>
-) -> Result<Option<Vec<String>>, String> {
+) -> Result<Option<Vec<String>>, GuruError> {
     use tauri_plugin_dialog::DialogExt;
     
     let mut dialog = app.dialog().file();
@@ -253,7 +323,7 @@ async fn open_file_dialog(
 }
 
 #[tauri::command]
-async fn open_folder_dialog(app: tauri::AppHandle) -> Result<Option<String>, String> {
+async fn open_folder_dialog(app: tauri::AppHandle) -> Result<Option<String>, GuruError> {
     use tauri_plugin_dialog::DialogExt;
     
     let path = app.dialog().file().blocking_pick_folder();
@@ -266,25 +336,25 @@ async fn open_folder_dialog(app: tauri::AppHandle) -> Result<Option<String>, Str
 
 // File utilities
 #[tauri::command]
-async fn read_file_as_base64(file_path: String) -> Result<String, String> {
+async fn read_file_as_base64(file_path: String) -> Result<String, GuruError> {
     use std::fs;
     use base64::{Engine as _, engine::general_purpose};
     
     match fs::read(&file_path) {
         Ok(contents) => Ok(general_purpose::STANDARD.encode(contents)),
-        Err(e) => Err(format!("Failed to read file: {}", e))
+        Err(e) => Err(format!("Failed to read file: {}", e).into())
     }
 }
 
 // File browser support
 #[tauri::command]
-async fn scan_directory(dir_path: String) -> Result<Value, String> {
-    execute_guru_command("scanDirectory".to_string(), vec![serde_json::json!(dir_path)]).await
+async fn scan_directory(state: tauri::State<'_, AppState>, dir_path: String) -> Result<Value, GuruError> {
+    execute_guru_command(state, "scanDirectory".to_string(), vec![serde_json::json!(dir_path)]).await
 }
 
 // Model management commands
 #[tauri::command]
-async fn check_model_status() -> Result<ModelStatus, String> {
+async fn check_model_status() -> Result<ModelStatus, GuruError> {
     let app_data = dirs::data_dir()
         .ok_or("Could not find app data directory")?;
     
@@ -312,84 +382,106 @@ async fn check_model_status() -> Result<ModelStatus, String> {
     }
 }
 
+// Default model download location, matching check_model_status's layout
+fn default_model_path() -> Result<std::path::PathBuf, GuruError> {
+    let app_data = dirs::data_dir().ok_or("Could not find app data directory")?;
+    Ok(app_data.join("guru").join("models").join("phi4-mini").join("model.onnx"))
+}
+
+// Default location of the analysis/KB-metadata cache, alongside the model directory
+fn default_cache_dir() -> Result<std::path::PathBuf, GuruError> {
+    let app_data = dirs::data_dir().ok_or("Could not find app data directory")?;
+    Ok(app_data.join("guru").join("cache"))
+}
+
+/// Drop every cached analysis result and KB metadata entry, for users
+/// managing disk usage
 #[tauri::command]
-async fn download_model(window: tauri::Window) -> Result<DownloadResult, String> {
-    use std::io::{BufRead, BufReader};
-    use std::thread;
-    
-    // Use the existing model-downloader.cjs script
-    let mut cmd = Command::new("node");
-    cmd.arg("../scripts/model-downloader.cjs");
-    cmd.stdout(std::process::Stdio::piped());
-    cmd.stderr(std::process::Stdio::piped());
-    
-    let mut child = cmd.spawn()
-        .map_err(|e| format!("Failed to start download: {}", e))?;
-    
-    let window_clone = window.clone();
-    
-    // Handle stdout in a separate thread
-    let stdout_handle = if let Some(stdout) = child.stdout.take() {
-        Some(thread::spawn(move || {
-            let reader = BufReader::new(stdout);
-            for line in reader.lines() {
-                if let Ok(line) = line {
-                    eprintln!("Download output: {}", line);
-                    if let Ok(progress) = serde_json::from_str::<Value>(&line) {
-                        // Emit progress to frontend
-                        if let Err(e) = window_clone.emit("download-progress", progress) {
-                            eprintln!("Failed to emit progress: {}", e);
-                        }
-                    }
-                }
-            }
-        }))
-    } else {
-        None
-    };
-    
-    // Handle stderr in a separate thread
-    let stderr_handle = if let Some(stderr) = child.stderr.take() {
-        Some(thread::spawn(move || {
-            let reader = BufReader::new(stderr);
-            for line in reader.lines() {
-                if let Ok(line) = line {
-                    eprintln!("Download error: {}", line);
-                }
-            }
-        }))
-    } else {
-        None
-    };
-    
-    // Wait for the process to complete
-    let status = child.wait()
-        .map_err(|e| format!("Failed to wait for download: {}", e))?;
-    
-    // Wait for threads to finish
-    if let Some(handle) = stdout_handle {
-        let _ = handle.join();
-    }
-    if let Some(handle) = stderr_handle {
-        let _ = handle.join();
-    }
-    
-    if status.success() {
-        Ok(DownloadResult {
-            success: true,
-            error: None,
-        })
-    } else {
-        Ok(DownloadResult {
-            success: false,
-            error: Some("Download failed".to_string()),
-        })
+async fn clear_cache(state: tauri::State<'_, AppState>) -> Result<(), GuruError> {
+    state.cache.clear_all()
+}
+
+/// Entry counts and disk usage of the analysis/KB-metadata cache
+#[tauri::command]
+async fn cache_stats(state: tauri::State<'_, AppState>) -> Result<CacheStats, GuruError> {
+    Ok(state.cache.stats())
+}
+
+/// Download the Phi-4 Mini model natively: stream it in chunks to a `.part`
+/// file (resuming via `Range` if a partial download already exists), verify
+/// its SHA-256 against `expected_sha256` once complete, and only then
+/// atomically rename it into place. Replaces the previous shell-out to
+/// `model-downloader.cjs`, which only reported success/failure with no
+/// integrity check or resume support.
+#[tauri::command]
+async fn download_model(
+    window: tauri::Window,
+    state: tauri::State<'_, AppState>,
+    url: String,
+    expected_sha256: Option<String>,
+) -> Result<DownloadResult, GuruError> {
+    let target_path = default_model_path()?;
+
+    let cancel = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+    *state.download_cancel.lock().unwrap() = Some(cancel.clone());
+
+    let result = model_downloader::download_model_native(
+        &window,
+        &url,
+        &target_path,
+        expected_sha256.as_deref(),
+        cancel,
+    )
+    .await?;
+
+    *state.download_cancel.lock().unwrap() = None;
+
+    Ok(DownloadResult {
+        success: result.success,
+        error: result.error,
+    })
+}
+
+/// Abort an in-progress `download_model`, leaving the `.part` file on disk
+/// so a later call can resume from where it left off
+#[tauri::command]
+async fn cancel_download(state: tauri::State<'_, AppState>) -> Result<(), GuruError> {
+    if let Some(cancel) = state.download_cancel.lock().unwrap().as_ref() {
+        cancel.store(true, std::sync::atomic::Ordering::SeqCst);
     }
+    Ok(())
+}
+
+/// Run Phi-4 analysis on project data, initializing the engine on first use
+#[tauri::command]
+async fn run_phi4_analysis(project_data: Value) -> Result<Value, GuruError> {
+    phi4_integration::run_phi4_analysis(project_data)
+        .await
+        .map_err(GuruError::from)
+}
+
+/// Which execution provider the Phi-4 engine actually selected, so the
+/// frontend can report whether acceleration engaged instead of assuming the
+/// `use_gpu` config flag held. `None` until the engine has been initialized
+/// (e.g. by a prior `run_phi4_analysis` or `learn_phi4_from_corpus` call).
+#[tauri::command]
+async fn phi4_acceleration_status() -> Result<Option<phi4_mini::ExecutionProvider>, GuruError> {
+    Ok(phi4_integration::phi4_acceleration_status())
+}
+
+/// Train the Phi-4 engine on a corpus of prior analyses (same shape as
+/// `run_phi4_analysis`'s `project_data`), persisting the calibrated state
+/// so future analyses benefit immediately
+#[tauri::command]
+async fn learn_phi4_from_corpus(corpus: Vec<Value>) -> Result<(), GuruError> {
+    phi4_integration::learn_phi4_from_corpus(corpus)
+        .await
+        .map_err(GuruError::from)
 }
 
 // Backend management
 #[tauri::command]
-async fn start_guru_service(state: tauri::State<'_, AppState>) -> Result<(), String> {
+async fn start_guru_service(state: tauri::State<'_, AppState>) -> Result<(), GuruError> {
     let mut backend = state.backend_process.lock().unwrap();
     
     if backend.is_some() {
@@ -405,71 +497,40 @@ async fn start_guru_service(state: tauri::State<'_, AppState>) -> Result<(), Str
             *backend = Some(child);
             Ok(())
         }
-        Err(e) => Err(format!("Failed to start backend: {}", e)),
+        Err(e) => Err(format!("Failed to start backend: {}", e).into()),
     }
 }
 
 #[tauri::command]
-async fn stop_guru_service(state: tauri::State<'_, AppState>) -> Result<(), String> {
+async fn stop_guru_service(state: tauri::State<'_, AppState>) -> Result<(), GuruError> {
     let mut backend = state.backend_process.lock().unwrap();
     
     if let Some(mut child) = backend.take() {
         match child.kill() {
             Ok(_) => Ok(()),
-            Err(e) => Err(format!("Failed to stop backend: {}", e)),
+            Err(e) => Err(format!("Failed to stop backend: {}", e).into()),
         }
     } else {
         Ok(()) // Not running
     }
 }
 
-// Direct file analysis
+// Direct file analysis, routed through the persistent BackendManager
+// connection instead of a one-shot TCP connect-write-read-first-line
 #[tauri::command]
-async fn analyze_files(files: Vec<String>, batch_mode: bool) -> Result<String, String> {
-    use std::io::Write;
-    use std::net::TcpStream;
-    
-    // Connect to the backend service
-    let mut stream = TcpStream::connect("127.0.0.1:3456")
-        .map_err(|e| format!("Failed to connect to backend: {}. Make sure the Guru service is running.", e))?;
-    
-    // Send analysis request
-    let request = serde_json::json!({
-        "type": "analyze",
-        "files": files,
-        "batchMode": batch_mode,
-    });
-    
-    let request_str = serde_json::to_string(&request)
-        .map_err(|e| format!("Failed to serialize request: {}", e))?;
-    
-    stream.write_all(request_str.as_bytes())
-        .map_err(|e| format!("Failed to send request: {}", e))?;
-    
-    stream.write_all(b"\n")
-        .map_err(|e| format!("Failed to send newline: {}", e))?;
-    
-    // Read response
-    use std::io::BufRead;
-    let reader = std::io::BufReader::new(stream);
-    let mut response = String::new();
-    
-    for line in reader.lines() {
-        match line {
-            Ok(line) => {
-                response = line;
-                break;
-            }
-            Err(e) => return Err(format!("Failed to read response: {}", e)),
-        }
-    }
-    
-    Ok(response)
+async fn analyze_files(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+    files: Vec<String>,
+    batch_mode: bool,
+) -> Result<String, GuruError> {
+    let result = state.backend.send_analyze(files, batch_mode, &app).await?;
+    serde_json::to_string(&result).map_err(GuruError::from)
 }
 
 // Select folder dialog
 #[tauri::command]
-async fn select_folder(app: tauri::AppHandle) -> Result<Option<String>, String> {
+async fn select_folder(app: tauri::AppHandle) -> Result<Option<String>, GuruError> {
     use tauri_plugin_dialog::DialogExt;
     
     let path = app.dialog().file().blocking_pick_folder();
@@ -482,7 +543,7 @@ async fn select_folder(app: tauri::AppHandle) -> Result<Option<String>, String>
 
 // Read directory contents
 #[tauri::command]
-async fn read_directory(path: String) -> Result<Vec<FileItem>, String> {
+async fn read_directory(path: String) -> Result<Vec<FileItem>, GuruError> {
     use std::fs;
     
     let entries = fs::read_dir(&path)
@@ -521,87 +582,210 @@ async fn read_directory(path: String) -> Result<Vec<FileItem>, String> {
 
 // Guru core features
 #[tauri::command]
-async fn get_evolving_tasks() -> Result<Value, String> {
-    execute_guru_command("getEvolvingTasks".to_string(), vec![]).await
+async fn get_evolving_tasks(state: tauri::State<'_, AppState>) -> Result<Value, GuruError> {
+    execute_guru_command(state, "getEvolvingTasks".to_string(), vec![]).await
 }
 
 #[tauri::command]
-async fn get_quantum_memories() -> Result<Value, String> {
-    execute_guru_command("getQuantumMemories".to_string(), vec![]).await
+async fn get_quantum_memories(state: tauri::State<'_, AppState>) -> Result<Value, GuruError> {
+    execute_guru_command(state, "getQuantumMemories".to_string(), vec![]).await
 }
 
 #[tauri::command]
-async fn get_suggestions() -> Result<Value, String> {
-    execute_guru_command("getSuggestions".to_string(), vec![]).await
+async fn get_suggestions(state: tauri::State<'_, AppState>) -> Result<Value, GuruError> {
+    execute_guru_command(state, "getSuggestions".to_string(), vec![]).await
 }
 
 #[tauri::command]
-async fn execute_mcp_tool(tool: String, args: Value) -> Result<Value, String> {
-    use std::io::{Write, Read};
-    use std::net::TcpStream;
-    
-    // Connect to the MCP server
-    let mut stream = TcpStream::connect("127.0.0.1:3457")
-        .map_err(|e| format!("Failed to connect to MCP server: {}. Make sure the Guru MCP service is running.", e))?;
-    
-    // Send MCP tool request
-    let request = serde_json::json!({
-        "jsonrpc": "2.0",
-        "method": "tools/call",
-        "params": {
-            "name": tool,
-            "arguments": args
-        },
-        "id": 1
-    });
-    
-    let request_str = serde_json::to_string(&request)
-        .map_err(|e| format!("Failed to serialize request: {}", e))?;
-    
-    stream.write_all(request_str.as_bytes())
-        .map_err(|e| format!("Failed to send request: {}", e))?;
-    
-    stream.write_all(b"\n")
-        .map_err(|e| format!("Failed to send newline: {}", e))?;
-    
-    // Read the response
-    let mut response = String::new();
-    let mut buffer = [0; 1024];
-    
-    loop {
-        match stream.read(&mut buffer) {
-            Ok(0) => break, // Connection closed
-            Ok(n) => {
-                response.push_str(&String::from_utf8_lossy(&buffer[..n]));
-                if response.contains('\n') {
-                    break;
+async fn execute_mcp_tool(state: tauri::State<'_, AppState>, tool: String, args: Value) -> Result<Value, GuruError> {
+    state.mcp.send_mcp_tool_call(&tool, &args).await.map_err(GuruError::from)
+}
+
+/// Cache key for a tool call, so identical calls within a session can reuse
+/// a prior result instead of re-running expensive tools
+fn mcp_cache_key(name: &str, args: &Value) -> String {
+    format!("{}:{}", name, args.to_string())
+}
+
+/// Drive a multi-step function-calling loop against the model backend: send
+/// the prompt plus the tool schema list, execute any tool calls it returns
+/// over the MCP socket, feed the results back, and repeat until the model
+/// returns a final text answer.
+///
+/// `may_`-prefixed tools are treated as side-effecting actions: instead of
+/// dispatching immediately, we emit `mcp-confirmation-request` and block on
+/// `confirm_mcp_tool_call` before calling them.
+#[tauri::command]
+async fn run_mcp_agent(
+    window: tauri::Window,
+    state: tauri::State<'_, AppState>,
+    prompt: String,
+    tools: Vec<Value>,
+) -> Result<Value, GuruError> {
+    const MAX_STEPS: usize = 10;
+
+    let mut messages = vec![serde_json::json!({ "role": "user", "content": prompt })];
+
+    for step in 0..MAX_STEPS {
+        let _ = window.emit(
+            "agent-step",
+            AgentStepEvent {
+                step,
+                kind: "thinking".to_string(),
+                tool: None,
+                detail: serde_json::json!({ "messages": messages.len() }),
+            },
+        );
+
+        let step_result = execute_guru_command(
+            state.clone(),
+            "runAgentStep".to_string(),
+            vec![
+                serde_json::json!({ "messages": messages, "tools": tools }),
+            ],
+        )
+        .await?;
+
+        let calls: Vec<McpToolCall> = step_result
+            .get("toolCalls")
+            .and_then(|v| serde_json::from_value(v.clone()).ok())
+            .unwrap_or_default();
+
+        if calls.is_empty() {
+            let final_text = step_result
+                .get("text")
+                .cloned()
+                .unwrap_or(Value::Null);
+
+            let _ = window.emit(
+                "agent-step",
+                AgentStepEvent {
+                    step,
+                    kind: "final".to_string(),
+                    tool: None,
+                    detail: final_text.clone(),
+                },
+            );
+
+            return Ok(final_text);
+        }
+
+        messages.push(serde_json::json!({ "role": "assistant", "tool_calls": calls }));
+
+        for call in calls {
+            let cache_key = mcp_cache_key(&call.name, &call.arguments);
+
+            let result = if let Some(cached) = state.mcp_tool_cache.lock().unwrap().get(&cache_key).cloned() {
+                let _ = window.emit(
+                    "agent-step",
+                    AgentStepEvent {
+                        step,
+                        kind: "tool-cache-hit".to_string(),
+                        tool: Some(call.name.clone()),
+                        detail: cached.clone(),
+                    },
+                );
+                Ok(cached)
+            } else if call.name.starts_with("may_") {
+                let request_id = state
+                    .next_confirmation_id
+                    .fetch_add(1, std::sync::atomic::Ordering::SeqCst)
+                    .to_string();
+                let (tx, rx) = tokio::sync::oneshot::channel();
+                state
+                    .pending_confirmations
+                    .lock()
+                    .unwrap()
+                    .insert(request_id.clone(), tx);
+
+                let _ = window.emit(
+                    "mcp-confirmation-request",
+                    McpConfirmationRequest {
+                        request_id: request_id.clone(),
+                        tool: call.name.clone(),
+                        arguments: call.arguments.clone(),
+                    },
+                );
+
+                let approved = rx.await.unwrap_or(false);
+
+                if approved {
+                    state.mcp.send_mcp_tool_call(&call.name, &call.arguments).await
+                } else {
+                    Ok(serde_json::json!({ "denied": true }))
                 }
+            } else {
+                state.mcp.send_mcp_tool_call(&call.name, &call.arguments).await
+            };
+
+            let result = result.unwrap_or_else(|e| serde_json::json!({ "error": e }));
+
+            if !call.name.starts_with("may_") {
+                state
+                    .mcp_tool_cache
+                    .lock()
+                    .unwrap()
+                    .insert(cache_key, result.clone());
             }
-            Err(e) => return Err(format!("Failed to read response: {}", e)),
+
+            let _ = window.emit(
+                "agent-step",
+                AgentStepEvent {
+                    step,
+                    kind: "tool-result".to_string(),
+                    tool: Some(call.name.clone()),
+                    detail: result.clone(),
+                },
+            );
+
+            messages.push(serde_json::json!({
+                "role": "tool",
+                "name": call.name,
+                "content": result,
+            }));
         }
     }
-    
-    // Parse the response
-    let result: Value = serde_json::from_str(&response)
-        .map_err(|e| format!("Failed to parse response: {}", e))?;
-    
-    // Extract the result from JSON-RPC response
-    if let Some(error) = result.get("error") {
-        return Err(format!("MCP error: {}", error));
-    }
-    
-    if let Some(result_value) = result.get("result") {
-        Ok(result_value.clone())
-    } else {
-        Err("Invalid MCP response format".to_string())
-    }
+
+    Err(GuruError::new(
+        GuruErrorCode::Unknown,
+        "Agent loop exceeded maximum steps without a final answer",
+    ))
+}
+
+/// Resolve a pending `may_`-prefixed tool confirmation raised by `run_mcp_agent`
+#[tauri::command]
+async fn confirm_mcp_tool_call(
+    state: tauri::State<'_, AppState>,
+    request_id: String,
+    approved: bool,
+) -> Result<(), GuruError> {
+    let sender = state
+        .pending_confirmations
+        .lock()
+        .unwrap()
+        .remove(&request_id)
+        .ok_or_else(|| format!("No pending confirmation for request id: {}", request_id))?;
+
+    sender
+        .send(approved)
+        .map_err(|_| GuruError::new(GuruErrorCode::Unknown, "Confirmation receiver was already dropped"))
 }
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
+    let cache_dir = default_cache_dir().expect("Could not resolve analysis cache directory");
+    let cache = AnalysisCache::open_or_fallback(&cache_dir);
+
     tauri::Builder::default()
         .manage(AppState {
             backend_process: Mutex::new(None),
+            pending_confirmations: Mutex::new(HashMap::new()),
+            mcp_tool_cache: Mutex::new(HashMap::new()),
+            backend: Arc::new(BackendManager::new("127.0.0.1:3456", "backend")),
+            mcp: Arc::new(BackendManager::new("127.0.0.1:3457", "mcp")),
+            download_cancel: Mutex::new(None),
+            cache: Arc::new(cache),
+            next_confirmation_id: std::sync::atomic::AtomicU64::new(1),
         })
         .setup(|app| {
             // Start the backend service when the app starts
@@ -611,6 +795,13 @@ pub fn run() {
                     eprintln!("Failed to start Guru service: {}", e);
                 }
             });
+
+            // Maintain the WebSocket gateways to both endpoints, reconnecting
+            // with backoff on drop and reporting state via Tauri events.
+            let state = app.state::<AppState>();
+            state.backend.start(app.handle().clone());
+            state.mcp.start(app.handle().clone());
+
             Ok(())
         })
         .on_window_event(|_window, event| {
@@ -646,16 +837,24 @@ pub fn run() {
             get_quantum_memories,
             get_suggestions,
             execute_mcp_tool,
+            run_mcp_agent,
+            confirm_mcp_tool_call,
             open_file_dialog,
             open_folder_dialog,
             scan_directory,
             check_model_status,
             download_model,
+            cancel_download,
+            run_phi4_analysis,
+            phi4_acceleration_status,
+            learn_phi4_from_corpus,
             start_guru_service,
             stop_guru_service,
             analyze_files,
             select_folder,
-            read_directory
+            read_directory,
+            clear_cache,
+            cache_stats
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -0,0 +1,153 @@
+use futures_util::StreamExt;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::io::{Read, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+use tauri::Emitter;
+
+/// Outcome of a native, resumable model download
+#[derive(Debug, Serialize)]
+pub struct NativeDownloadResult {
+    pub success: bool,
+    pub error: Option<String>,
+}
+
+/// Progress emitted periodically while streaming the download body, so the
+/// UI can render percent complete and throughput during the multi-GB transfer
+#[derive(Debug, Serialize, Clone)]
+struct DownloadProgressEvent {
+    downloaded_bytes: u64,
+    total_bytes: Option<u64>,
+    percent: Option<f64>,
+    bytes_per_sec: f64,
+}
+
+/// Stream `url` to `target_path` in chunks, writing to a sibling `.part`
+/// file and resuming from its existing length via a `Range` header if one
+/// is already present. On completion, verifies the incrementally computed
+/// SHA-256 against `expected_sha256` (when given) and only then atomically
+/// renames the `.part` file into place.
+pub async fn download_model_native(
+    window: &tauri::Window,
+    url: &str,
+    target_path: &Path,
+    expected_sha256: Option<&str>,
+    cancel: Arc<AtomicBool>,
+) -> Result<NativeDownloadResult, String> {
+    let part_path = target_path.with_extension("part");
+
+    if let Some(parent) = target_path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create model directory: {}", e))?;
+    }
+
+    let mut downloaded: u64 = part_path.metadata().map(|m| m.len()).unwrap_or(0);
+
+    // Hash the bytes already on disk from a prior interrupted attempt so the
+    // final checksum covers the whole file, not just this session's chunks.
+    let mut hasher = Sha256::new();
+    if downloaded > 0 {
+        let mut existing = std::fs::File::open(&part_path)
+            .map_err(|e| format!("Failed to reopen partial download for hashing: {}", e))?;
+        let mut buffer = [0u8; 1_048_576];
+        loop {
+            let n = existing
+                .read(&mut buffer)
+                .map_err(|e| format!("Failed to read partial download: {}", e))?;
+            if n == 0 {
+                break;
+            }
+            hasher.update(&buffer[..n]);
+        }
+    }
+
+    let client = reqwest::Client::new();
+    let mut request = client.get(url);
+    if downloaded > 0 {
+        request = request.header("Range", format!("bytes={}-", downloaded));
+    }
+
+    let response = request
+        .send()
+        .await
+        .map_err(|e| format!("Failed to start download: {}", e))?;
+
+    let status = response.status();
+    if !status.is_success() && status.as_u16() != 206 {
+        return Err(format!("Download request failed with status {}", status));
+    }
+
+    // A server that ignores our Range header and restarts from byte 0 would
+    // silently corrupt the resumed file, so only trust `downloaded` as an
+    // offset when the server actually confirmed a partial response.
+    if downloaded > 0 && status.as_u16() != 206 {
+        downloaded = 0;
+        hasher = Sha256::new();
+        let _ = std::fs::remove_file(&part_path);
+    }
+
+    let total_bytes = response.content_length().map(|len| len + downloaded);
+
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&part_path)
+        .map_err(|e| format!("Failed to open partial download file: {}", e))?;
+
+    let start = Instant::now();
+    let mut last_emit = Instant::now();
+    let mut stream = response.bytes_stream();
+
+    while let Some(chunk) = stream.next().await {
+        if cancel.load(Ordering::SeqCst) {
+            return Ok(NativeDownloadResult {
+                success: false,
+                error: Some("Download cancelled".to_string()),
+            });
+        }
+
+        let chunk = chunk.map_err(|e| format!("Failed to read download chunk: {}", e))?;
+        file.write_all(&chunk)
+            .map_err(|e| format!("Failed to write download chunk: {}", e))?;
+        hasher.update(&chunk);
+        downloaded += chunk.len() as u64;
+
+        if last_emit.elapsed().as_millis() >= 200 {
+            let elapsed = start.elapsed().as_secs_f64().max(0.001);
+            let _ = window.emit(
+                "download-progress",
+                DownloadProgressEvent {
+                    downloaded_bytes: downloaded,
+                    total_bytes,
+                    percent: total_bytes.map(|t| (downloaded as f64 / t as f64) * 100.0),
+                    bytes_per_sec: downloaded as f64 / elapsed,
+                },
+            );
+            last_emit = Instant::now();
+        }
+    }
+
+    drop(file);
+
+    if let Some(expected) = expected_sha256 {
+        let actual = format!("{:x}", hasher.finalize());
+        if actual != expected {
+            let _ = std::fs::remove_file(&part_path);
+            return Err(format!(
+                "Downloaded file checksum mismatch: expected {}, got {}",
+                expected, actual
+            ));
+        }
+    }
+
+    std::fs::rename(&part_path, target_path)
+        .map_err(|e| format!("Failed to finalize download: {}", e))?;
+
+    Ok(NativeDownloadResult {
+        success: true,
+        error: None,
+    })
+}
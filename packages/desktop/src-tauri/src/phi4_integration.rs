@@ -1,67 +1,179 @@
-use phi4_mini::{Phi4MiniEngine, Phi4Config, Phi4Analysis};
+use phi4_mini::{AnalyticInput, AnalyticService, ExecutionProvider, LearningResults, Phi4Analysis, Phi4Config, Phi4MiniEngine};
+use std::path::PathBuf;
 use std::sync::Arc;
 use tokio::sync::Mutex;
 use serde_json::Value;
 
-/// Global Phi-4 engine instance
-static PHI4_ENGINE: once_cell::sync::OnceCell<Arc<Mutex<Phi4MiniEngine>>> = once_cell::sync::OnceCell::new();
+/// Global analytic service instance. Wraps the Phi-4 engine as the always-on
+/// fallback unit so callers keep going through `AnalyticUnit::analyze`
+/// rather than the concrete engine — swapping in a cheaper unit ahead of it
+/// later (e.g. a threshold detector) won't require touching this file again.
+static PHI4_ENGINE: once_cell::sync::OnceCell<Arc<Mutex<AnalyticService>>> = once_cell::sync::OnceCell::new();
+
+/// The execution provider requested for the `Phi4MiniEngine` whose
+/// `AnalyticService` ended up installed in `PHI4_ENGINE`, kept alongside it
+/// since `AnalyticService` only exposes the engine through the boxed
+/// `AnalyticUnit` trait, not the concrete type. Only ever set right after
+/// `PHI4_ENGINE.set` succeeds, so the two stay describing the same engine
+/// even if `initialize_phi4_engine` races.
+static PHI4_SELECTED_PROVIDER: once_cell::sync::OnceCell<ExecutionProvider> = once_cell::sync::OnceCell::new();
+
+/// The execution provider requested for the Phi-4 engine (see
+/// `Phi4MiniEngine::selected_provider` — this is what was asked for, not a
+/// confirmation the hardware/driver for it was actually present), so
+/// callers can report it instead of assuming the `use_gpu` config held.
+/// Returns `None` before the engine has been initialized.
+pub fn phi4_acceleration_status() -> Option<ExecutionProvider> {
+    PHI4_SELECTED_PROVIDER.get().copied()
+}
+
+fn phi4_app_dir() -> Result<PathBuf, String> {
+    Ok(dirs::data_local_dir()
+        .ok_or("Failed to get local data directory")?
+        .join("guru")
+        .join("models")
+        .join("phi4-mini"))
+}
+
+/// Where learned state (calibrated confidence priors, pattern
+/// fingerprints, ...) is persisted between runs
+fn learned_state_path(app_dir: &std::path::Path) -> PathBuf {
+    app_dir.join("learned_state.bin")
+}
 
 /// Initialize the Phi-4 engine
 pub async fn initialize_phi4_engine() -> Result<(), String> {
     if PHI4_ENGINE.get().is_some() {
         return Ok(()); // Already initialized
     }
-    
-    // Get model path from app data directory
-    let app_dir = dirs::data_local_dir()
-        .ok_or("Failed to get local data directory")?
-        .join("guru")
-        .join("models")
-        .join("phi4-mini");
-    
+
+    let app_dir = phi4_app_dir()?;
+
     let config = Phi4Config {
         model_path: app_dir.join("model.onnx").to_string_lossy().to_string(),
         tokenizer_path: app_dir.join("tokenizer.json").to_string_lossy().to_string(),
         max_length: 2048,
         temperature: 0.7,
         num_threads: 4,
-        use_gpu: false, // CPU for now
+        use_gpu: true,
+        preferred_providers: Vec::new(),
     };
-    
+
     match Phi4MiniEngine::with_config(config).await {
         Ok(engine) => {
-            PHI4_ENGINE.set(Arc::new(Mutex::new(engine)))
+            let provider = engine.selected_provider();
+            let mut service = AnalyticService::new(Box::new(engine));
+
+            // Reuse calibrated state from a previous run if any was saved
+            if let Some(state) = LearningResults::load_from(&learned_state_path(&app_dir)) {
+                service.load_state(state);
+            }
+
+            PHI4_ENGINE.set(Arc::new(Mutex::new(service)))
                 .map_err(|_| "Failed to set global engine")?;
+            // Only recorded once this thread's engine won the race above, so
+            // it always describes whichever engine ended up installed
+            let _ = PHI4_SELECTED_PROVIDER.set(provider);
             Ok(())
         }
         Err(e) => Err(format!("Failed to initialize Phi-4 engine: {}", e))
     }
 }
 
+/// Train the engine on `corpus` (each entry shaped like the `project_data`
+/// passed to `run_phi4_analysis`), then persist and immediately apply the
+/// resulting calibrated state so later analyses benefit right away.
+///
+/// Trains one corpus entry at a time, re-acquiring the service lock
+/// between inferences, so a long corpus doesn't block concurrent
+/// `run_phi4_analysis` calls for its entire duration.
+pub async fn learn_phi4_from_corpus(corpus: Vec<Value>) -> Result<(), String> {
+    initialize_phi4_engine().await?;
+    let service = PHI4_ENGINE.get().ok_or("Phi-4 engine not initialized")?;
+
+    // Mirror run_phi4_analysis's prompt shape exactly, so calibration is
+    // learned against the same input distribution it's later scored on
+    let inputs: Vec<AnalyticInput> = corpus
+        .into_iter()
+        .map(|project_data| {
+            let system_prompt = project_data["systemPrompt"]
+                .as_str()
+                .unwrap_or("You are an expert project analyst");
+            let analysis_prompt = project_data["analysisPrompt"]
+                .as_str()
+                .unwrap_or("Analyze this project");
+            let prompt = format!("{}\n\n{}", system_prompt, analysis_prompt);
+            AnalyticInput { prompt, project_data }
+        })
+        .collect();
+
+    let mut per_item_results = Vec::new();
+    for input in &inputs {
+        let single = {
+            let mut guard = service.lock().await;
+            guard.learn(std::slice::from_ref(input)).await
+        };
+        match single {
+            // An empty result means the item carried no calibration signal
+            // (e.g. its response never parsed as structured JSON) rather
+            // than a failure — don't let it count as "learned from"
+            Ok(result) if !result.calibrated_confidence_priors.is_empty() => {
+                per_item_results.push(result)
+            }
+            Ok(_) => log::warn!("Skipping corpus item while learning: no calibration signal"),
+            Err(e) => log::warn!("Skipping corpus item while learning: {e}"),
+        }
+    }
+
+    if per_item_results.is_empty() {
+        return Err("No corpus items could be learned from".to_string());
+    }
+
+    // Combine rather than re-deriving the averaging/union logic here — it
+    // already lives in `LearningResults::combine`, shared with any other
+    // caller that trains one item at a time for the same lock-fairness reason
+    let results = LearningResults::combine(per_item_results);
+
+    // Apply immediately so this session benefits even if persisting fails
+    service.lock().await.load_state(results.clone());
+
+    if let Err(e) = results.save_to(&learned_state_path(&phi4_app_dir()?)) {
+        log::warn!("Failed to persist learned state (still applied for this session): {e}");
+    }
+
+    Ok(())
+}
+
 /// Run Phi-4 analysis on project data
 pub async fn run_phi4_analysis(project_data: Value) -> Result<Value, String> {
     // Ensure engine is initialized
     initialize_phi4_engine().await?;
-    
-    let engine = PHI4_ENGINE.get()
+
+    let service = PHI4_ENGINE.get()
         .ok_or("Phi-4 engine not initialized")?;
-    
-    let engine = engine.lock().await;
-    
+
+    let service = service.lock().await;
+
     // Extract prompt from project data
     let system_prompt = project_data["systemPrompt"]
         .as_str()
         .unwrap_or("You are an expert project analyst");
-    
+
     let analysis_prompt = project_data["analysisPrompt"]
         .as_str()
         .unwrap_or("Analyze this project");
-    
+
     // Combine prompts
     let full_prompt = format!("{}\n\n{}", system_prompt, analysis_prompt);
-    
-    // Run cognitive analysis
-    match engine.cognitive_analysis(&full_prompt).await {
+
+    let input = AnalyticInput {
+        prompt: full_prompt,
+        project_data,
+    };
+
+    // Dispatch through the trait so any future non-LLM units registered
+    // ahead of the Phi-4 fallback are considered first
+    match service.analyze(&input).await {
         Ok(analysis) => {
             // Convert Phi4Analysis to JSON response format expected by frontend
             let response = serde_json::json!({
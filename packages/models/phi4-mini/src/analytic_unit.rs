@@ -0,0 +1,168 @@
+use serde_json::Value;
+
+use crate::{LearningResults, Phi4Analysis, Phi4MiniEngine, Phi4Result};
+
+// Add async-trait dependency to Cargo.toml
+
+/// Input handed to an `AnalyticUnit`. The two fields are independent so a
+/// unit can opt into whichever shape it understands: `prompt` for
+/// prose/LLM-style units (Phi-4), `project_data` for structured/metric
+/// units (e.g. a threshold detector scanning numeric series).
+#[derive(Debug, Clone, Default)]
+pub struct AnalyticInput {
+    pub prompt: String,
+    pub project_data: Value,
+}
+
+/// A swappable source of `Phi4Analysis` output. `Phi4MiniEngine` is one
+/// implementation (the always-applicable LLM fallback); lightweight,
+/// non-LLM detectors (threshold scans, rule checks, ...) can implement this
+/// too and be tried first via `AnalyticService`, so a request only pays for
+/// the 2-4GB ONNX model when nothing cheaper could answer it.
+#[async_trait::async_trait]
+pub trait AnalyticUnit: Send + Sync {
+    /// Stable identifier for this unit, for logging/telemetry
+    fn id(&self) -> &str;
+
+    /// Whether this unit can meaningfully analyze `input`. `AnalyticService`
+    /// calls this on each registered unit in order and dispatches to the
+    /// first match.
+    fn supports(&self, input: &AnalyticInput) -> bool;
+
+    async fn analyze(&self, input: &AnalyticInput) -> Phi4Result<Phi4Analysis>;
+
+    /// Train on `corpus`, producing reusable state (calibrated confidence
+    /// priors, pattern fingerprints, threshold baselines, ...) that
+    /// `load_state` can later restore without retraining. Optional: units
+    /// with nothing to learn can leave the default no-op.
+    async fn learn(&mut self, _corpus: &[AnalyticInput]) -> Phi4Result<LearningResults> {
+        Ok(LearningResults::new())
+    }
+
+    /// Restore state previously produced by `learn` (typically reloaded
+    /// from disk at startup). Optional: default is a no-op.
+    fn load_state(&mut self, _state: LearningResults) {}
+}
+
+#[async_trait::async_trait]
+impl AnalyticUnit for Phi4MiniEngine {
+    fn id(&self) -> &str {
+        "phi4-mini"
+    }
+
+    /// The LLM fallback: always applicable, since it only needs a prompt
+    /// string (which is never empty-checked — an empty prompt still
+    /// produces a response, just an unfocused one)
+    fn supports(&self, _input: &AnalyticInput) -> bool {
+        true
+    }
+
+    async fn analyze(&self, input: &AnalyticInput) -> Phi4Result<Phi4Analysis> {
+        self.cognitive_analysis(&input.prompt).await
+    }
+
+    /// Run each corpus prompt through cognitive analysis and fold the
+    /// results into a calibrated confidence prior (replacing the
+    /// hard-coded fallback confidence) and the set of distinct patterns
+    /// observed
+    async fn learn(&mut self, corpus: &[AnalyticInput]) -> Phi4Result<LearningResults> {
+        let mut results = LearningResults::new();
+        if corpus.is_empty() {
+            return Ok(results);
+        }
+
+        let mut confidences = Vec::with_capacity(corpus.len());
+        let mut fingerprints = Vec::new();
+        for input in corpus {
+            // One bad/transient inference shouldn't discard everything
+            // already learned from the rest of the corpus
+            match self.cognitive_analysis(&input.prompt).await {
+                // `parse_cognitive_response`'s JSON-parse-failure fallback
+                // sets both fields to the same raw response text, which a
+                // genuinely parsed analysis practically never does. Corpus
+                // items that hit the fallback carry no real training
+                // signal (a calibrated-or-default confidence echoed back
+                // at itself) and would just teach the model to trust its
+                // own fallback guess, so they're excluded here.
+                Ok(analysis) if analysis.mathematical_insights != analysis.raw_response => {
+                    confidences.push(analysis.confidence);
+                    fingerprints.extend(analysis.pattern_detection.detected_patterns);
+                }
+                Ok(_) => log::warn!(
+                    "Skipping corpus item during learn(): response didn't parse as structured JSON"
+                ),
+                Err(e) => log::warn!("Skipping corpus item during learn(): {e}"),
+            }
+        }
+        if confidences.is_empty() {
+            return Ok(results);
+        }
+        fingerprints.sort();
+        fingerprints.dedup();
+
+        let calibrated_confidence = confidences.iter().sum::<f32>() / confidences.len() as f32;
+        results
+            .calibrated_confidence_priors
+            .insert(self.id().to_string(), calibrated_confidence);
+        results.pattern_fingerprints.insert(self.id().to_string(), fingerprints);
+
+        Ok(results)
+    }
+
+    fn load_state(&mut self, state: LearningResults) {
+        if let Some(&prior) = state.calibrated_confidence_priors.get(self.id()) {
+            self.set_calibrated_confidence_prior(prior);
+        }
+    }
+}
+
+/// Registry that routes an `AnalyticInput` to the first registered unit
+/// whose `supports()` returns true, falling back to a designated unit
+/// (normally the Phi-4 engine) when none of them match.
+pub struct AnalyticService {
+    units: Vec<Box<dyn AnalyticUnit>>,
+    fallback: Box<dyn AnalyticUnit>,
+}
+
+impl AnalyticService {
+    pub fn new(fallback: Box<dyn AnalyticUnit>) -> Self {
+        Self {
+            units: Vec::new(),
+            fallback,
+        }
+    }
+
+    /// Register a unit to be tried, in registration order, before the
+    /// fallback. Later registrations are tried later.
+    pub fn register(&mut self, unit: Box<dyn AnalyticUnit>) {
+        self.units.push(unit);
+    }
+
+    pub async fn analyze(&self, input: &AnalyticInput) -> Phi4Result<Phi4Analysis> {
+        for unit in &self.units {
+            if unit.supports(input) {
+                return unit.analyze(input).await;
+            }
+        }
+        self.fallback.analyze(input).await
+    }
+
+    /// Train the fallback unit on `corpus`. Registered non-fallback units
+    /// aren't trained here since they're typically deterministic (e.g.
+    /// `ThresholdAnalyticUnit`) rather than learned.
+    pub async fn learn(&mut self, corpus: &[AnalyticInput]) -> Phi4Result<LearningResults> {
+        self.fallback.learn(corpus).await
+    }
+
+    /// Restore previously learned state into the fallback unit
+    pub fn load_state(&mut self, state: LearningResults) {
+        self.fallback.load_state(state);
+    }
+
+    /// Id of the fallback unit, so callers (e.g. a training routine
+    /// accumulating `LearningResults` across many locking sessions) can key
+    /// state keyed by `AnalyticUnit::id` without depending on a concrete type
+    pub fn fallback_id(&self) -> &str {
+        self.fallback.id()
+    }
+}
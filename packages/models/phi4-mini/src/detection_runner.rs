@@ -0,0 +1,315 @@
+use std::collections::{HashSet, VecDeque};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex as StdMutex};
+use std::time::Duration;
+
+use tokio::sync::{mpsc, Mutex};
+use tokio::task::JoinHandle;
+
+use crate::analytic_unit::{AnalyticInput, AnalyticService};
+use crate::Phi4Analysis;
+
+// Add tokio dependency (features = ["rt", "sync", "time"]) to Cargo.toml
+
+/// Tick cadence and retained-history size for a `DetectionRunner`
+#[derive(Debug, Clone, Copy)]
+pub struct DetectionRunnerConfig {
+    pub interval_ms: u64,
+    pub window: usize,
+}
+
+/// Runs an `AnalyticService` continuously on a fixed interval instead of
+/// requiring a fresh one-shot call per analysis. Inputs are queued with
+/// `push`; each tick analyzes only what's arrived since the previous tick
+/// (tracked by `last_processed`) and retains at most `config.window` of the
+/// most recent inputs, so a long-running session doesn't grow the feed
+/// unbounded. Pattern de-duplication is rolling rather than permanent: a
+/// pattern is only suppressed while it keeps showing up tick over tick,
+/// and re-triggers if it disappears for a tick and then comes back — so a
+/// metric that recovers and later regresses again still gets reported.
+pub struct DetectionRunner {
+    service: Arc<Mutex<AnalyticService>>,
+    // `Arc` so a tick's collection of retained inputs is a refcount bump,
+    // not a deep clone of each input's `project_data` JSON tree
+    feed: Arc<Mutex<VecDeque<Arc<AnalyticInput>>>>,
+    total_pushed: Arc<AtomicU64>,
+    last_processed: Arc<AtomicU64>,
+    /// Pattern names carried over from the most recently processed tick
+    carried_patterns: Arc<Mutex<HashSet<String>>>,
+    config: DetectionRunnerConfig,
+    handle: StdMutex<Option<JoinHandle<()>>>,
+    running: Arc<AtomicBool>,
+}
+
+impl DetectionRunner {
+    pub fn new(service: Arc<Mutex<AnalyticService>>, config: DetectionRunnerConfig) -> Self {
+        Self {
+            service,
+            feed: Arc::new(Mutex::new(VecDeque::new())),
+            total_pushed: Arc::new(AtomicU64::new(0)),
+            last_processed: Arc::new(AtomicU64::new(0)),
+            carried_patterns: Arc::new(Mutex::new(HashSet::new())),
+            config,
+            handle: StdMutex::new(None),
+            running: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Queue a new input to be picked up on the next tick, evicting the
+    /// oldest retained input once the feed exceeds `config.window`
+    pub async fn push(&self, input: AnalyticInput) {
+        let mut feed = self.feed.lock().await;
+        feed.push_back(Arc::new(input));
+        while feed.len() > self.config.window {
+            feed.pop_front();
+        }
+        let pushed = self.total_pushed.fetch_add(1, Ordering::SeqCst) + 1;
+
+        let processed = self.last_processed.load(Ordering::SeqCst);
+        let backlog = pushed.saturating_sub(processed);
+        if backlog > self.config.window as u64 {
+            log::warn!(
+                "DetectionRunner feed backlog ({backlog}) exceeds window ({}); {} unprocessed input(s) evicted before a tick could analyze them",
+                self.config.window,
+                backlog - self.config.window as u64
+            );
+        }
+    }
+
+    /// Start the tick loop, returning the receiving half of the channel
+    /// each tick's new `Phi4Analysis` results are emitted on. A prior run
+    /// started by this instance is stopped first.
+    pub fn start(&self) -> mpsc::UnboundedReceiver<Phi4Analysis> {
+        self.stop();
+
+        let (tx, rx) = mpsc::unbounded_channel();
+        self.running.store(true, Ordering::SeqCst);
+
+        let service = self.service.clone();
+        let feed = self.feed.clone();
+        let total_pushed = self.total_pushed.clone();
+        let last_processed = self.last_processed.clone();
+        let carried_patterns = self.carried_patterns.clone();
+        let running = self.running.clone();
+        let interval_ms = self.config.interval_ms;
+
+        let task = tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(Duration::from_millis(interval_ms));
+
+            'ticks: loop {
+                ticker.tick().await;
+                if !running.load(Ordering::SeqCst) {
+                    break;
+                }
+
+                let processed = last_processed.load(Ordering::SeqCst);
+
+                // Read `total_pushed` under the same `feed` lock `push()`
+                // updates it under, so the two stay consistent even if a
+                // `push()` lands concurrently with this tick
+                let (inputs, pushed) = {
+                    let feed = feed.lock().await;
+                    let pushed = total_pushed.load(Ordering::SeqCst);
+                    if pushed <= processed {
+                        (Vec::new(), pushed)
+                    } else {
+                        // Items evicted from the bounded window can no
+                        // longer be replayed; only process what's retained
+                        let retained = feed.len();
+                        let new_count = (pushed - processed).min(retained as u64) as usize;
+                        (feed.iter().skip(retained - new_count).cloned().collect(), pushed)
+                    }
+                };
+                last_processed.store(pushed, Ordering::SeqCst);
+                if inputs.is_empty() {
+                    continue;
+                }
+
+                // Patterns seen across *this* tick's inputs replace
+                // `carried_patterns` wholesale once the tick finishes, so a
+                // pattern that stops appearing for a tick is no longer
+                // suppressed if it reappears later
+                let carried = carried_patterns.lock().await.clone();
+                let mut this_tick_patterns = HashSet::new();
+
+                for input in inputs {
+                    let analyzed = {
+                        let service = service.lock().await;
+                        service.analyze(&input).await
+                    };
+                    let Ok(mut analysis) = analyzed else {
+                        continue;
+                    };
+
+                    let raw_patterns: Vec<(String, f32)> = analysis
+                        .pattern_detection
+                        .detected_patterns
+                        .iter()
+                        .cloned()
+                        .zip(analysis.pattern_detection.confidence_scores.iter().copied())
+                        .collect();
+
+                    if raw_patterns.is_empty() {
+                        // Nothing pattern-based to coalesce; forward as-is
+                        if tx.send(analysis).is_err() {
+                            running.store(false, Ordering::SeqCst);
+                            break 'ticks;
+                        }
+                        continue;
+                    }
+
+                    let mut kept_patterns = Vec::new();
+                    let mut kept_scores = Vec::new();
+                    for (pattern, score) in &raw_patterns {
+                        this_tick_patterns.insert(pattern.clone());
+                        if !carried.contains(pattern) {
+                            kept_patterns.push(pattern.clone());
+                            kept_scores.push(*score);
+                        }
+                    }
+
+                    if kept_patterns.is_empty() {
+                        // Every pattern here is still the same ongoing
+                        // breach already reported on a prior tick
+                        continue;
+                    }
+
+                    analysis.confidence = kept_scores.iter().sum::<f32>() / kept_scores.len() as f32;
+                    analysis.pattern_detection.detected_patterns = kept_patterns;
+                    analysis.pattern_detection.confidence_scores = kept_scores;
+
+                    if tx.send(analysis).is_err() {
+                        running.store(false, Ordering::SeqCst);
+                        break 'ticks;
+                    }
+                }
+
+                *carried_patterns.lock().await = this_tick_patterns;
+            }
+        });
+
+        *self.handle.lock().unwrap() = Some(task);
+        rx
+    }
+
+    /// Stop the tick loop started by `start`. Safe to call even if the
+    /// runner was never started, or has already been stopped.
+    pub fn stop(&self) {
+        self.running.store(false, Ordering::SeqCst);
+        if let Some(handle) = self.handle.lock().unwrap().take() {
+            handle.abort();
+        }
+    }
+}
+
+// Add tokio "test-util" dev-dependency feature to Cargo.toml for
+// `#[tokio::test(start_paused = true)]` and `tokio::time::advance`
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analytic_unit::AnalyticUnit;
+    use crate::{Phi4Error, Phi4Result};
+
+    /// Analytic unit that echoes back whatever pattern names are listed
+    /// under `project_data.patterns`, so a test can control exactly which
+    /// patterns a tick's inputs "detect"
+    struct EchoUnit;
+
+    #[async_trait::async_trait]
+    impl AnalyticUnit for EchoUnit {
+        fn id(&self) -> &str {
+            "echo"
+        }
+
+        fn supports(&self, _input: &AnalyticInput) -> bool {
+            true
+        }
+
+        async fn analyze(&self, input: &AnalyticInput) -> Phi4Result<Phi4Analysis> {
+            let patterns = input.project_data["patterns"]
+                .as_array()
+                .ok_or_else(|| Phi4Error::InvalidInput("missing patterns".to_string()))?;
+
+            let mut analysis = Phi4Analysis::new(1.0, "echo".to_string());
+            for pattern in patterns {
+                let name = pattern.as_str().ok_or_else(|| Phi4Error::InvalidInput("pattern not a string".to_string()))?;
+                analysis.add_pattern(name.to_string(), 1.0);
+            }
+            Ok(analysis)
+        }
+    }
+
+    fn runner(window: usize, interval_ms: u64) -> DetectionRunner {
+        let service = Arc::new(Mutex::new(AnalyticService::new(Box::new(EchoUnit))));
+        DetectionRunner::new(service, DetectionRunnerConfig { interval_ms, window })
+    }
+
+    fn input_with_patterns(patterns: &[&str]) -> AnalyticInput {
+        AnalyticInput {
+            prompt: String::new(),
+            project_data: serde_json::json!({ "patterns": patterns }),
+        }
+    }
+
+    /// Advance the paused clock past one tick interval and give the
+    /// runner's spawned task a chance to run to completion, so a
+    /// subsequent `rx.try_recv()` sees whatever that tick produced
+    async fn advance_one_tick(interval_ms: u64) {
+        tokio::time::advance(Duration::from_millis(interval_ms)).await;
+        for _ in 0..100 {
+            tokio::task::yield_now().await;
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn evicts_backlog_beyond_window_before_first_tick() {
+        let runner = runner(2, 10);
+        // push more inputs than `window` retains before any tick runs
+        for i in 0..5 {
+            runner.push(input_with_patterns(&[&format!("p{i}")])).await;
+        }
+
+        let mut rx = runner.start();
+        advance_one_tick(10).await;
+
+        let mut received = Vec::new();
+        while let Ok(analysis) = rx.try_recv() {
+            received.push(analysis);
+        }
+
+        // only the most recently retained `window` inputs survive the backlog
+        assert_eq!(received.len(), 2);
+        runner.stop();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn carried_pattern_is_suppressed_then_resurfaces_after_a_gap() {
+        let runner = runner(4, 10);
+
+        runner.push(input_with_patterns(&["p1"])).await;
+        let mut rx = runner.start();
+        advance_one_tick(10).await;
+        let first = rx.try_recv().expect("first tick should emit an analysis");
+        assert_eq!(first.pattern_detection.detected_patterns, vec!["p1"]);
+
+        // same pattern again next tick: already carried over, so it's
+        // coalesced away instead of being reported a second time
+        runner.push(input_with_patterns(&["p1"])).await;
+        advance_one_tick(10).await;
+        assert!(rx.try_recv().is_err());
+
+        // pattern disappears for a tick...
+        runner.push(input_with_patterns(&["other"])).await;
+        advance_one_tick(10).await;
+        let third = rx.try_recv().expect("unrelated pattern should still be reported");
+        assert_eq!(third.pattern_detection.detected_patterns, vec!["other"]);
+
+        // ...then comes back: no longer carried, so it's reported again
+        runner.push(input_with_patterns(&["p1"])).await;
+        advance_one_tick(10).await;
+        let fourth = rx.try_recv().expect("pattern should resurface after a gap");
+        assert_eq!(fourth.pattern_detection.detected_patterns, vec!["p1"]);
+
+        runner.stop();
+    }
+}
@@ -1,38 +1,243 @@
 use std::path::Path;
-use std::process::Command;
+use std::process::{Command, Stdio};
+use std::sync::Arc;
 use anyhow::{Result, Context};
 use log::{info, warn, debug};
 
 use crate::Phi4Error;
 
-/// Download and quantize Phi-4 Mini model to ONNX format
-pub async fn download_phi4_model(target_path: &str) -> Result<(), Phi4Error> {
-    info!("📥 Downloading Phi-4 Mini model...");
-    
+/// Coarse progress signal surfaced during the long-running download/convert
+/// pipeline, for callers rendering a TUI or CI progress indicator
+#[derive(Debug, Clone)]
+pub enum ConversionProgress {
+    /// Download percent complete, where known
+    Downloading(u8),
+    /// Converting the loaded model to ONNX
+    Converting,
+    /// Running a post-export quantization pass
+    Quantizing,
+    /// Saving the converted model/tokenizer to disk
+    Saving,
+    /// A raw line forwarded from the underlying Python process
+    Log(String),
+}
+
+/// Callback invoked with each `ConversionProgress` update
+pub type ProgressCallback = Arc<dyn Fn(ConversionProgress) + Send + Sync>;
+
+/// Spawn `python3 <script_path>`, stream stdout/stderr line-by-line through
+/// the crate's `log` macros in real time, and forward a coarse progress
+/// signal to `progress` instead of buffering everything until the process
+/// exits (as `Command::output()` does).
+fn run_streaming(script_path: &str, progress: Option<&ProgressCallback>) -> Result<(), Phi4Error> {
+    use std::io::{BufRead, BufReader};
+    use std::thread;
+
+    let mut child = Command::new("python3")
+        .arg(script_path)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("Failed to spawn conversion script")
+        .map_err(|e| Phi4Error::ModelNotFound(e.to_string()))?;
+
+    let stdout = child.stdout.take();
+    let stderr = child.stderr.take();
+
+    let stdout_progress = progress.cloned();
+    let stdout_handle = stdout.map(|out| {
+        thread::spawn(move || {
+            for line in BufReader::new(out).lines().flatten() {
+                info!("{}", line);
+                if let Some(callback) = &stdout_progress {
+                    callback(classify_progress_line(&line));
+                }
+            }
+        })
+    });
+
+    // Python's `logging` writes to stderr by default, so the conversion
+    // script's stage markers arrive on this stream
+    let stderr_progress = progress.cloned();
+    let stderr_handle = stderr.map(|err| {
+        thread::spawn(move || {
+            for line in BufReader::new(err).lines().flatten() {
+                warn!("{}", line);
+                if let Some(callback) = &stderr_progress {
+                    callback(classify_progress_line(&line));
+                }
+            }
+        })
+    });
+
+    let status = child
+        .wait()
+        .context("Failed to wait for conversion script")
+        .map_err(|e| Phi4Error::ModelNotFound(e.to_string()))?;
+
+    if let Some(handle) = stdout_handle {
+        let _ = handle.join();
+    }
+    if let Some(handle) = stderr_handle {
+        let _ = handle.join();
+    }
+
+    if !status.success() {
+        return Err(Phi4Error::ModelNotFound(format!(
+            "Script {} failed with status {}",
+            script_path, status
+        )));
+    }
+
+    Ok(())
+}
+
+/// Map a line of Python `logging` output onto a coarse `ConversionProgress`
+/// signal by looking for the stage markers the conversion scripts log
+fn classify_progress_line(line: &str) -> ConversionProgress {
+    if line.contains("Converting to ONNX") {
+        ConversionProgress::Converting
+    } else if line.contains("quantiz") || line.contains("Quantiz") {
+        ConversionProgress::Quantizing
+    } else if line.contains("Saving") || line.contains("saved") {
+        ConversionProgress::Saving
+    } else {
+        ConversionProgress::Log(line.to_string())
+    }
+}
+
+/// Supported small-but-capable models this crate can download and convert
+///
+/// Each variant carries the metadata needed to build the right export recipe
+/// — Optimum's ONNX export is model-specific, and MoE variants in particular
+/// need custom handling for their router/expert layers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phi4Variant {
+    /// Phi-4 Mini (the crate's original, default model)
+    Phi4Mini,
+    /// Phi-3.5 Mini Instruct
+    Phi35,
+    /// Phi-3.5 MoE Instruct (mixture-of-experts)
+    Phi35Moe,
+}
+
+impl Phi4Variant {
+    /// Hugging Face repo id to download from
+    pub fn hf_repo_id(&self) -> &'static str {
+        match self {
+            Phi4Variant::Phi4Mini => "microsoft/Phi-4",
+            Phi4Variant::Phi35 => "microsoft/Phi-3.5-mini-instruct",
+            Phi4Variant::Phi35Moe => "microsoft/Phi-3.5-MoE-instruct",
+        }
+    }
+
+    /// Expected download size for user feedback, quantized vs. full model
+    pub fn expected_download_size(&self) -> &'static str {
+        match self {
+            Phi4Variant::Phi4Mini => "~2-4GB (quantized from 14GB full model)",
+            Phi4Variant::Phi35 => "~2GB (quantized from 7.6GB full model)",
+            Phi4Variant::Phi35Moe => "~10-12GB (quantized from 42GB full model)",
+        }
+    }
+
+    /// Filename the fast-tokenizer JSON is saved under
+    pub fn tokenizer_filename(&self) -> &'static str {
+        match self {
+            Phi4Variant::Phi4Mini => "phi4-tokenizer.json",
+            Phi4Variant::Phi35 => "phi35-tokenizer.json",
+            Phi4Variant::Phi35Moe => "phi35-moe-tokenizer.json",
+        }
+    }
+
+    /// ONNX opset version to export with
+    pub fn opset_version(&self) -> u32 {
+        match self {
+            Phi4Variant::Phi4Mini | Phi4Variant::Phi35 => 14,
+            // The MoE router's top-k expert selection needs ops only
+            // available from opset 17 onward.
+            Phi4Variant::Phi35Moe => 17,
+        }
+    }
+
+    /// Whether the HF repo requires `trust_remote_code=True` to load
+    pub fn requires_trust_remote_code(&self) -> bool {
+        matches!(self, Phi4Variant::Phi35Moe)
+    }
+
+    /// Whether this variant needs a custom Optimum export config to handle
+    /// non-standard layers (e.g. MoE router/expert layers)
+    pub fn custom_export_config(&self) -> Option<&'static str> {
+        match self {
+            Phi4Variant::Phi35Moe => Some("Phi3MoEOnnxConfig"),
+            _ => None,
+        }
+    }
+}
+
+/// Quantization mode applied to the exported ONNX graph
+///
+/// Unlike the PyTorch-side `load_in_4bit=True` flag, these modes run as a
+/// post-export pass over the fp32 ONNX graph, so they work the same way on
+/// CPU-only machines without `bitsandbytes`/CUDA.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuantMode {
+    /// Skip quantization entirely; keep the exported fp32 ONNX model
+    None,
+    /// Dynamic quantization via `onnxruntime.quantization.quantize_dynamic`,
+    /// picking the weight type automatically from the graph's op types
+    Dynamic,
+    /// 4-bit quantization at PyTorch load time (requires CUDA/`bitsandbytes`)
+    Static4Bit,
+}
+
+/// Download and quantize a Phi model variant to ONNX format
+///
+/// `revision` pins the download to a specific HF git branch, tag, or commit
+/// hash instead of silently tracking `main`. The resolved commit hash is
+/// recorded alongside the model so `verify_model_files` can confirm the
+/// on-disk model matches the requested revision.
+pub async fn download_phi4_model(
+    target_path: &str,
+    variant: Phi4Variant,
+    quant_mode: QuantMode,
+    revision: Option<&str>,
+    progress: Option<ProgressCallback>,
+) -> Result<(), Phi4Error> {
+    info!(
+        "📥 Downloading {} model (revision: {})...",
+        variant.hf_repo_id(),
+        revision.unwrap_or("main")
+    );
+
     let parent_dir = Path::new(target_path)
         .parent()
         .ok_or_else(|| Phi4Error::ModelNotFound("Invalid target path".to_string()))?;
-    
+
     // Create models directory if it doesn't exist
     if !parent_dir.exists() {
         std::fs::create_dir_all(parent_dir)
             .context("Failed to create models directory")
             .map_err(|e| Phi4Error::ModelNotFound(e.to_string()))?;
     }
-    
+
     // Check if we need to install dependencies
     ensure_python_dependencies().await?;
-    
+
     // Download and convert model
-    download_and_convert_model(target_path).await?;
-    
+    download_and_convert_model(target_path, variant, quant_mode, revision, progress.as_ref()).await?;
+
     // Download tokenizer
-    download_tokenizer(parent_dir).await?;
-    
-    info!("✅ Phi-4 Mini model ready at: {}", target_path);
+    download_tokenizer(parent_dir, variant, revision, progress.as_ref()).await?;
+
+    info!("✅ {} model ready at: {}", variant.hf_repo_id(), target_path);
     Ok(())
 }
 
+/// Path to the sidecar file recording the resolved HF commit hash for a model
+fn revision_marker_path(target_path: &str) -> std::path::PathBuf {
+    Path::new(target_path).with_extension("revision.json")
+}
+
 /// Ensure required Python dependencies are installed
 async fn ensure_python_dependencies() -> Result<(), Phi4Error> {
     info!("🔧 Checking Python dependencies...");
@@ -72,43 +277,155 @@ async fn ensure_python_dependencies() -> Result<(), Phi4Error> {
     Ok(())
 }
 
-/// Download and convert Phi-4 model to ONNX with quantization
-async fn download_and_convert_model(target_path: &str) -> Result<(), Phi4Error> {
-    info!("🔄 Converting Phi-4 Mini to ONNX with 4-bit quantization...");
-    
+/// Download and convert a Phi model variant to ONNX with quantization
+async fn download_and_convert_model(
+    target_path: &str,
+    variant: Phi4Variant,
+    quant_mode: QuantMode,
+    revision: Option<&str>,
+    progress: Option<&ProgressCallback>,
+) -> Result<(), Phi4Error> {
+    info!("🔄 Converting {} to ONNX ({:?})...", variant.hf_repo_id(), quant_mode);
+
     // Create Python script for model conversion
-    let conversion_script = create_conversion_script(target_path);
+    let conversion_script = create_conversion_script(target_path, variant, quant_mode, revision);
     let script_path = "/tmp/phi4_convert.py";
-    
+
     std::fs::write(script_path, conversion_script)
         .context("Failed to write conversion script")
         .map_err(|e| Phi4Error::ModelNotFound(e.to_string()))?;
-    
-    // Run conversion script
-    let output = Command::new("python3")
-        .arg(script_path)
-        .output()
-        .context("Failed to run conversion script")
-        .map_err(|e| Phi4Error::ModelNotFound(e.to_string()))?;
-    
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(Phi4Error::ModelNotFound(
-            format!("Model conversion failed: {}", stderr)
-        ));
-    }
-    
+
+    // Stream stdout/stderr line-by-line instead of buffering until exit, since
+    // this is the longest-running operation in the crate (multi-minute,
+    // multi-GB) and callers need live feedback.
+    run_streaming(script_path, progress)?;
+
     // Clean up script
     let _ = std::fs::remove_file(script_path);
-    
+
+    // Record the checksum of the freshly converted model alongside the
+    // revision marker so a later `verify_model_files` can detect corruption.
+    record_checksum(target_path)?;
+
+    if let Some(callback) = progress {
+        callback(ConversionProgress::Saving);
+    }
+
     info!("✅ Model converted successfully");
     Ok(())
 }
 
+/// Compute and store the SHA-256 checksum of a freshly downloaded model in
+/// its revision marker file
+fn record_checksum(model_path: &str) -> Result<(), Phi4Error> {
+    let checksum = compute_sha256(model_path)?;
+    let marker_path = revision_marker_path(model_path);
+
+    let mut marker: serde_json::Value = if marker_path.exists() {
+        let contents = std::fs::read_to_string(&marker_path)
+            .context("Failed to read revision marker")
+            .map_err(|e| Phi4Error::ModelNotFound(e.to_string()))?;
+        serde_json::from_str(&contents).unwrap_or_else(|_| serde_json::json!({}))
+    } else {
+        serde_json::json!({})
+    };
+
+    marker["sha256"] = serde_json::Value::String(checksum);
+
+    std::fs::write(&marker_path, marker.to_string())
+        .context("Failed to write revision marker")
+        .map_err(|e| Phi4Error::ModelNotFound(e.to_string()))?;
+
+    Ok(())
+}
+
+/// Compute the SHA-256 checksum of a file
+fn compute_sha256(path: &str) -> Result<String, Phi4Error> {
+    use sha2::{Digest, Sha256};
+    use std::io::Read;
+
+    let mut file = std::fs::File::open(path)
+        .context("Failed to open file for checksum")
+        .map_err(|e| Phi4Error::ModelNotFound(e.to_string()))?;
+
+    let mut hasher = Sha256::new();
+    let mut buffer = [0u8; 1_048_576]; // 1MB chunks
+
+    loop {
+        let bytes_read = file
+            .read(&mut buffer)
+            .context("Failed to read file for checksum")
+            .map_err(|e| Phi4Error::ModelNotFound(e.to_string()))?;
+
+        if bytes_read == 0 {
+            break;
+        }
+
+        hasher.update(&buffer[..bytes_read]);
+    }
+
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
 /// Create Python script for model conversion
-fn create_conversion_script(target_path: &str) -> String {
+fn create_conversion_script(
+    target_path: &str,
+    variant: Phi4Variant,
+    quant_mode: QuantMode,
+    revision: Option<&str>,
+) -> String {
+    let revision_literal = match revision {
+        Some(rev) => format!("\"{}\"", rev),
+        None => "None".to_string(),
+    };
+    let revision_marker_path = revision_marker_path(target_path).display().to_string();
+    let model_name = variant.hf_repo_id();
+    let trust_remote_code = if variant.requires_trust_remote_code() { "True" } else { "False" };
+    let opset_version = variant.opset_version();
+    // MoE variants route through a custom Optimum export config so the
+    // router/expert layers don't fail export on unsupported sparse-routing
+    // ops; other variants use Optimum's default config for the model.
+    let export_config = match variant.custom_export_config() {
+        Some(config_cls) => format!(
+            r#"
+        from optimum.exporters.onnx.model_configs import {config_cls}
+        export_config = {config_cls}(model.config, task="text-generation-with-past")"#,
+            config_cls = config_cls
+        ),
+        None => "\n        export_config = None".to_string(),
+    };
+    // `Static4Bit` keeps the original CUDA-only `load_in_4bit=True` load path;
+    // everything else loads the model at full precision and, for `Dynamic`,
+    // quantizes the exported ONNX graph afterwards so CPU-only users get a
+    // working quantized model without `bitsandbytes`.
+    let load_in_4bit = matches!(quant_mode, QuantMode::Static4Bit);
+    let post_export_quantization = match quant_mode {
+        QuantMode::Dynamic => r#"
+        # Post-export dynamic quantization: pick the weight type by inspecting
+        # which ops in the exported graph are integer-quantizable.
+        logger.info("🔧 Running post-export dynamic quantization...")
+        from onnxruntime.quantization import quantize_dynamic, QuantType
+        from onnxruntime.quantization.registry import IntegerOpsRegistry
+
+        exported_model = onnx.load(target_path)
+        heavy_ops = {"MatMul", "Gemm"}
+        quantizable_ops = {node.op_type for node in exported_model.graph.node if node.op_type in heavy_ops}
+        if quantizable_ops and quantizable_ops.issubset(IntegerOpsRegistry.keys()):
+            weight_type = QuantType.QInt8
+        else:
+            weight_type = QuantType.QUInt8  # safer default when int8 ops aren't all available
+
+        quantized_path = target_path
+        quantize_dynamic(target_path, quantized_path, weight_type=weight_type)
+        logger.info(f"✅ Quantized with weight_type={weight_type}")
+"#.to_string(),
+        QuantMode::None | QuantMode::Static4Bit => String::new(),
+    };
+
     format!(r#"
+import json
 import torch
+import onnx
 from transformers import AutoModelForCausalLM, AutoTokenizer
 from optimum.onnxruntime import ORTModelForCausalLM
 import os
@@ -118,46 +435,53 @@ logging.basicConfig(level=logging.INFO)
 logger = logging.getLogger(__name__)
 
 def convert_phi4_to_onnx():
-    model_name = "microsoft/Phi-4"
+    model_name = "{model_name}"
+    revision = {revision_literal}
     target_path = "{target_path}"
     target_dir = os.path.dirname(target_path)
-    
-    logger.info("🔄 Starting Phi-4 Mini conversion...")
-    
+    opset_version = {opset_version}
+
+    logger.info("🔄 Starting %s conversion (revision=%s)...", model_name, revision)
+
     try:
         # Load tokenizer
         logger.info("📝 Loading tokenizer...")
-        tokenizer = AutoTokenizer.from_pretrained(model_name, trust_remote_code=True)
-        
-        # Load model with 4-bit quantization
-        logger.info("🧠 Loading model with 4-bit quantization...")
+        tokenizer = AutoTokenizer.from_pretrained(model_name, revision=revision, trust_remote_code={trust_remote_code})
+
+        # Load model
+        logger.info("🧠 Loading model...")
         model = AutoModelForCausalLM.from_pretrained(
             model_name,
+            revision=revision,
             torch_dtype=torch.float16,
             device_map="auto",
-            load_in_4bit=True,
-            trust_remote_code=True,
+            load_in_4bit={load_in_4bit},
+            trust_remote_code={trust_remote_code},
             attn_implementation="flash_attention_2" if torch.cuda.is_available() else "eager"
-        )
-        
+        ){export_config}
+
         # Convert to ONNX
-        logger.info("⚡ Converting to ONNX format...")
+        logger.info("⚡ Converting to ONNX format (opset %s)...", opset_version)
         ort_model = ORTModelForCausalLM.from_pretrained(
             model_name,
+            revision=revision,
             from_transformers=True,
             use_cache=True,
+            opset=opset_version,
+            trust_remote_code={trust_remote_code},
+            export_config=export_config,
             provider="CPUExecutionProvider"  # Use CPU for compatibility
         )
-        
+
         # Save ONNX model
         logger.info(f"💾 Saving ONNX model to {{target_dir}}")
         os.makedirs(target_dir, exist_ok=True)
         ort_model.save_pretrained(target_dir)
-        
+
         # Save tokenizer
-        tokenizer_path = os.path.join(target_dir, "phi4-tokenizer.json")
+        tokenizer_path = os.path.join(target_dir, "{tokenizer_filename}")
         tokenizer.save_pretrained(target_dir)
-        
+
         # Rename model file to match expected name
         model_files = [f for f in os.listdir(target_dir) if f.endswith('.onnx')]
         if model_files:
@@ -165,101 +489,309 @@ def convert_phi4_to_onnx():
             dst_path = target_path
             os.rename(src_path, dst_path)
             logger.info(f"✅ Model saved as {{dst_path}}")
-        
+{post_export_quantization}
+        # Record the resolved commit hash so verify_model_files can confirm
+        # the on-disk model matches the requested revision.
+        resolved_commit = getattr(model.config, "_commit_hash", None)
+        with open("{revision_marker_path}", "w") as f:
+            json.dump({{"requested_revision": revision, "resolved_commit": resolved_commit}}, f)
+
         logger.info("🎉 Conversion completed successfully!")
-        
+
     except Exception as e:
         logger.error(f"❌ Conversion failed: {{e}}")
         raise
 
 if __name__ == "__main__":
     convert_phi4_to_onnx()
-"#, target_path = target_path)
+"#,
+        target_path = target_path,
+        model_name = model_name,
+        revision_literal = revision_literal,
+        revision_marker_path = revision_marker_path,
+        opset_version = opset_version,
+        trust_remote_code = trust_remote_code,
+        export_config = export_config,
+        tokenizer_filename = variant.tokenizer_filename(),
+        load_in_4bit = if load_in_4bit { "True" } else { "False" },
+        post_export_quantization = post_export_quantization,
+    )
 }
 
 /// Download tokenizer separately
-async fn download_tokenizer(models_dir: &Path) -> Result<(), Phi4Error> {
-    info!("📝 Downloading tokenizer...");
-    
+async fn download_tokenizer(
+    models_dir: &Path,
+    variant: Phi4Variant,
+    revision: Option<&str>,
+    progress: Option<&ProgressCallback>,
+) -> Result<(), Phi4Error> {
+    info!("📝 Downloading tokenizer (revision: {})...", revision.unwrap_or("main"));
+
+    let revision_literal = match revision {
+        Some(rev) => format!("\"{}\"", rev),
+        None => "None".to_string(),
+    };
+    let trust_remote_code = if variant.requires_trust_remote_code() { "True" } else { "False" };
+
     // Create Python script for tokenizer download
     let tokenizer_script = format!(r#"
 from transformers import AutoTokenizer
 import os
 
-model_name = "microsoft/Phi-4"
-target_dir = "{}"
+model_name = "{model_name}"
+revision = {revision}
+target_dir = "{target_dir}"
 
 print("📝 Loading and saving tokenizer...")
-tokenizer = AutoTokenizer.from_pretrained(model_name, trust_remote_code=True)
+tokenizer = AutoTokenizer.from_pretrained(model_name, revision=revision, trust_remote_code={trust_remote_code})
 
-# Save as both formats for compatibility  
+# Save as both formats for compatibility
 tokenizer.save_pretrained(target_dir)
 
 # Also save as JSON for tokenizers crate
-tokenizer_json_path = os.path.join(target_dir, "phi4-tokenizer.json")
+tokenizer_json_path = os.path.join(target_dir, "{tokenizer_filename}")
 tokenizer.backend_tokenizer.save(tokenizer_json_path)
 
 print(f"✅ Tokenizer saved to {{target_dir}}")
-"#, models_dir.display());
+"#,
+        model_name = variant.hf_repo_id(),
+        revision = revision_literal,
+        target_dir = models_dir.display(),
+        trust_remote_code = trust_remote_code,
+        tokenizer_filename = variant.tokenizer_filename());
 
     let script_path = "/tmp/phi4_tokenizer.py";
     std::fs::write(script_path, tokenizer_script)
         .context("Failed to write tokenizer script")
         .map_err(|e| Phi4Error::ModelNotFound(e.to_string()))?;
-    
-    let output = Command::new("python3")
-        .arg(script_path)
-        .output()
-        .context("Failed to run tokenizer script")
-        .map_err(|e| Phi4Error::ModelNotFound(e.to_string()))?;
-    
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(Phi4Error::ModelNotFound(
-            format!("Tokenizer download failed: {}", stderr)
-        ));
-    }
-    
+
+    run_streaming(script_path, progress)?;
+
     // Clean up script
     let _ = std::fs::remove_file(script_path);
-    
+
+    if let Some(callback) = progress {
+        callback(ConversionProgress::Saving);
+    }
+
     info!("✅ Tokenizer ready");
     Ok(())
 }
 
+/// Expected top-level input/output names on the exported Phi-4 ONNX graph
+const EXPECTED_GRAPH_INPUTS: &[&str] = &["input_ids", "attention_mask"];
+const EXPECTED_GRAPH_OUTPUTS: &[&str] = &["logits"];
+
+/// Result of verifying the on-disk model and tokenizer files
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ModelVerification {
+    /// Model and tokenizer files exist, parse correctly, and match the
+    /// recorded checksum (and revision, if one was requested)
+    Valid,
+    /// One or both files are missing
+    Missing,
+    /// A file exists but is malformed (truncated ONNX graph, invalid
+    /// tokenizer JSON, or a revision mismatch), with a human-readable reason
+    Corrupt(String),
+    /// The model file exists and parses, but its SHA-256 no longer matches
+    /// the checksum recorded at download time
+    ChecksumMismatch { expected: String, actual: String },
+}
+
+impl ModelVerification {
+    /// Convenience check mirroring the previous boolean return value
+    pub fn is_valid(&self) -> bool {
+        matches!(self, ModelVerification::Valid)
+    }
+}
+
 /// Check if model files exist and are valid
-pub fn verify_model_files(model_path: &str, tokenizer_path: &str) -> Result<bool, Phi4Error> {
+///
+/// Unlike a bare file-size check, this parses the ONNX graph to confirm it's
+/// well-formed with the expected input/output names, and verifies the
+/// SHA-256 checksum recorded at download time. When `expected_revision` is
+/// set, it also confirms the on-disk model's recorded resolved commit hash
+/// matches the requested revision (branch, tag, or commit).
+pub fn verify_model_files(
+    model_path: &str,
+    tokenizer_path: &str,
+    expected_revision: Option<&str>,
+) -> Result<ModelVerification, Phi4Error> {
     let model_exists = Path::new(model_path).exists();
     let tokenizer_exists = Path::new(tokenizer_path).exists();
-    
+
     if !model_exists {
         debug!("Model file not found: {}", model_path);
-        return Ok(false);
+        return Ok(ModelVerification::Missing);
     }
-    
+
     if !tokenizer_exists {
         debug!("Tokenizer file not found: {}", tokenizer_path);
-        return Ok(false);
+        return Ok(ModelVerification::Missing);
     }
-    
-    // Check file sizes (basic validation)
+
+    // Parse the tokenizer JSON rather than just checking it exists, so a
+    // truncated or corrupt tokenizer file is caught early instead of failing
+    // later during inference.
+    if crate::tokenizer::Phi4Tokenizer::from_file(tokenizer_path).is_err() {
+        return Ok(ModelVerification::Corrupt(format!(
+            "Tokenizer file is not a valid tokenizer JSON: {}",
+            tokenizer_path
+        )));
+    }
+
+    // Parse the ONNX protobuf and graph to confirm it's well-formed with the
+    // expected input/output names, rather than trusting file size alone.
+    if let Err(reason) = validate_onnx_graph(model_path) {
+        return Ok(ModelVerification::Corrupt(reason));
+    }
+
+    // Verify the SHA-256 checksum recorded at download time.
+    if let Some(expected_checksum) = recorded_checksum(model_path)? {
+        let actual_checksum = compute_sha256(model_path)?;
+        if actual_checksum != expected_checksum {
+            warn!(
+                "Model checksum mismatch: expected {}, got {}",
+                expected_checksum, actual_checksum
+            );
+            return Ok(ModelVerification::ChecksumMismatch {
+                expected: expected_checksum,
+                actual: actual_checksum,
+            });
+        }
+    }
+
+    if let Some(expected) = expected_revision {
+        if !revision_matches(model_path, expected)? {
+            return Ok(ModelVerification::Corrupt(format!(
+                "Model revision does not match expected revision: {}",
+                expected
+            )));
+        }
+    }
+
     let model_size = std::fs::metadata(model_path)
         .context("Failed to get model file metadata")
         .map_err(|e| Phi4Error::ModelNotFound(e.to_string()))?
         .len();
-    
-    if model_size < 1_000_000 { // Less than 1MB is suspicious
-        warn!("Model file seems too small: {} bytes", model_size);
+
+    info!("✅ Model files verified ({}MB)", model_size / 1_000_000);
+    Ok(ModelVerification::Valid)
+}
+
+/// Parse the ONNX protobuf and graph to confirm the model is well-formed
+/// with the expected input/output names. Shells out to `onnx.checker` since
+/// that's the reference implementation of the ONNX validity rules.
+fn validate_onnx_graph(model_path: &str) -> Result<(), String> {
+    let script = format!(
+        r#"
+import sys
+import onnx
+
+try:
+    model = onnx.load("{path}")
+    onnx.checker.check_model(model)
+except Exception as e:
+    print(f"INVALID: {{e}}")
+    sys.exit(1)
+
+input_names = {{i.name for i in model.graph.input}}
+output_names = {{o.name for o in model.graph.output}}
+
+expected_inputs = {expected_inputs}
+expected_outputs = {expected_outputs}
+
+missing_inputs = expected_inputs - input_names
+missing_outputs = expected_outputs - output_names
+
+if missing_inputs or missing_outputs:
+    print(f"INVALID: missing inputs={{missing_inputs}} outputs={{missing_outputs}}")
+    sys.exit(1)
+
+print("VALID")
+"#,
+        path = model_path,
+        expected_inputs = python_set_literal(EXPECTED_GRAPH_INPUTS),
+        expected_outputs = python_set_literal(EXPECTED_GRAPH_OUTPUTS),
+    );
+
+    let script_path = "/tmp/phi4_verify_onnx.py";
+    std::fs::write(script_path, script).map_err(|e| format!("Failed to write verification script: {}", e))?;
+
+    let output = Command::new("python3")
+        .arg(script_path)
+        .output()
+        .map_err(|e| format!("Failed to run ONNX verification script: {}", e))?;
+
+    let _ = std::fs::remove_file(script_path);
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    if output.status.success() && stdout.trim() == "VALID" {
+        Ok(())
+    } else {
+        Err(format!("ONNX graph validation failed: {}", stdout.trim()))
+    }
+}
+
+/// Render a Rust string slice as a Python set literal
+fn python_set_literal(values: &[&str]) -> String {
+    let quoted: Vec<String> = values.iter().map(|v| format!("\"{}\"", v)).collect();
+    format!("{{{}}}", quoted.join(", "))
+}
+
+/// Read the SHA-256 checksum recorded in a model's revision marker, if any
+fn recorded_checksum(model_path: &str) -> Result<Option<String>, Phi4Error> {
+    let marker_path = revision_marker_path(model_path);
+
+    if !marker_path.exists() {
+        return Ok(None);
+    }
+
+    let contents = std::fs::read_to_string(&marker_path)
+        .context("Failed to read revision marker")
+        .map_err(|e| Phi4Error::ModelNotFound(e.to_string()))?;
+
+    let marker: serde_json::Value = serde_json::from_str(&contents)
+        .context("Failed to parse revision marker")
+        .map_err(|e| Phi4Error::ModelNotFound(e.to_string()))?;
+
+    Ok(marker.get("sha256").and_then(|v| v.as_str()).map(String::from))
+}
+
+/// Check the revision marker left by `create_conversion_script` against an
+/// expected HF revision (branch, tag, or resolved commit hash)
+fn revision_matches(model_path: &str, expected_revision: &str) -> Result<bool, Phi4Error> {
+    let marker_path = revision_marker_path(model_path);
+
+    if !marker_path.exists() {
+        debug!("No revision marker found at {}", marker_path.display());
         return Ok(false);
     }
-    
-    info!("✅ Model files verified ({}MB)", model_size / 1_000_000);
-    Ok(true)
+
+    let contents = std::fs::read_to_string(&marker_path)
+        .context("Failed to read revision marker")
+        .map_err(|e| Phi4Error::ModelNotFound(e.to_string()))?;
+
+    let marker: serde_json::Value = serde_json::from_str(&contents)
+        .context("Failed to parse revision marker")
+        .map_err(|e| Phi4Error::ModelNotFound(e.to_string()))?;
+
+    let matches = marker.get("requested_revision").and_then(|v| v.as_str()) == Some(expected_revision)
+        || marker.get("resolved_commit").and_then(|v| v.as_str()) == Some(expected_revision);
+
+    if !matches {
+        warn!(
+            "Model revision mismatch: expected {}, found {:?}",
+            expected_revision, marker
+        );
+    }
+
+    Ok(matches)
 }
 
 /// Get expected model download size for user feedback
-pub fn get_expected_download_size() -> &'static str {
-    "~2-4GB (quantized from 14GB full model)"
+pub fn get_expected_download_size(variant: Phi4Variant) -> &'static str {
+    variant.expected_download_size()
 }
 
 #[cfg(test)]
@@ -268,15 +800,77 @@ mod tests {
     
     #[test]
     fn test_conversion_script_generation() {
-        let script = create_conversion_script("/tmp/test-model.onnx");
+        let script = create_conversion_script("/tmp/test-model.onnx", Phi4Variant::Phi4Mini, QuantMode::Static4Bit, None);
         assert!(script.contains("microsoft/Phi-4"));
         assert!(script.contains("/tmp/test-model.onnx"));
         assert!(script.contains("load_in_4bit=True"));
+        assert!(script.contains("revision = None"));
     }
-    
+
+    #[test]
+    fn test_dynamic_quant_mode_adds_post_export_pass() {
+        let script = create_conversion_script("/tmp/test-model.onnx", Phi4Variant::Phi4Mini, QuantMode::Dynamic, None);
+        assert!(script.contains("load_in_4bit=False"));
+        assert!(script.contains("quantize_dynamic"));
+        assert!(script.contains("IntegerOpsRegistry"));
+    }
+
+    #[test]
+    fn test_conversion_script_pins_revision() {
+        let script = create_conversion_script("/tmp/test-model.onnx", Phi4Variant::Phi4Mini, QuantMode::Dynamic, Some("abc123"));
+        assert!(script.contains("revision = \"abc123\""));
+        assert!(script.contains("revision=revision"));
+    }
+
+    #[test]
+    fn test_moe_variant_script_uses_custom_export_config() {
+        let script = create_conversion_script("/tmp/test-model.onnx", Phi4Variant::Phi35Moe, QuantMode::None, None);
+        assert!(script.contains("microsoft/Phi-3.5-MoE-instruct"));
+        assert!(script.contains("Phi3MoEOnnxConfig"));
+        assert!(script.contains("trust_remote_code=True"));
+        assert!(script.contains("opset_version = 17"));
+    }
+
     #[test]
     fn test_expected_size() {
-        let size = get_expected_download_size();
+        let size = get_expected_download_size(Phi4Variant::Phi4Mini);
         assert!(size.contains("GB"));
     }
+
+    #[test]
+    fn test_verify_model_files_reports_missing() {
+        let result = verify_model_files("/nonexistent/model.onnx", "/nonexistent/tokenizer.json", None);
+        assert_eq!(result.unwrap(), ModelVerification::Missing);
+    }
+
+    #[test]
+    fn test_python_set_literal() {
+        assert_eq!(python_set_literal(&["a", "b"]), "{\"a\", \"b\"}");
+    }
+
+    #[test]
+    fn test_classify_progress_line() {
+        assert!(matches!(
+            classify_progress_line("⚡ Converting to ONNX format (opset 14)..."),
+            ConversionProgress::Converting
+        ));
+        assert!(matches!(
+            classify_progress_line("🔧 Running post-export dynamic quantization..."),
+            ConversionProgress::Quantizing
+        ));
+        assert!(matches!(
+            classify_progress_line("💾 Saving ONNX model to /tmp/models"),
+            ConversionProgress::Saving
+        ));
+        assert!(matches!(
+            classify_progress_line("some other log line"),
+            ConversionProgress::Log(_)
+        ));
+    }
+
+    #[test]
+    fn test_run_streaming_reports_missing_script() {
+        let result = run_streaming("/nonexistent/script.py", None);
+        assert!(result.is_err());
+    }
 }
\ No newline at end of file
@@ -1,16 +1,33 @@
 use std::path::Path;
-use ort::session::{Session, builder::GraphOptimizationLevel};
+use ort::session::{builder::{GraphOptimizationLevel, SessionBuilder}, Session};
+use ort::execution_providers::ExecutionProviderDispatch;
 use tokenizers::Tokenizer;
 use log::{info, debug, warn};
 
-use crate::{Phi4Config, Phi4Result, Phi4Error, Phi4Analysis, CognitiveAnalysis};
-use crate::generation::{TextGenerator, GenerationConfig};
+use crate::{Phi4Config, Phi4Result, Phi4Error, Phi4Analysis, CognitiveAnalysis, ExecutionProvider};
+use crate::generation::{TextGenerator, GenerationConfig, json_constraint, default_eos_token_ids};
+
+// Add ort "cuda", "directml", "coreml" Cargo features to Cargo.toml —
+// `register_execution_provider` below is cfg-gated per feature so the crate
+// still builds (falling back to CPU at runtime) on a manifest where only
+// some, or none, of them are enabled
 
 /// High-performance Phi-4 Mini inference engine using ONNX Runtime
 pub struct Phi4MiniEngine {
     session: Session,
     tokenizer: Tokenizer,
     config: Phi4Config,
+    /// Calibrated confidence learned via `AnalyticUnit::learn`/`load_state`,
+    /// used in place of the hard-coded fallback confidence in
+    /// `parse_cognitive_response` once available
+    calibrated_confidence_prior: Option<f32>,
+    /// The highest-priority execution provider registered for `session` —
+    /// `ExecutionProvider::Cpu` when `use_gpu` was false or no provider was
+    /// compiled in. Note this is what was *requested*: `ort` can still fall
+    /// back further (ultimately to CPU) at session-build time if the
+    /// hardware/driver for this provider isn't present, and that fallback
+    /// isn't observable from here — see `register_execution_provider`.
+    selected_provider: ExecutionProvider,
 }
 
 impl Phi4MiniEngine {
@@ -26,16 +43,30 @@ impl Phi4MiniEngine {
         // Check if model files exist
         if !Path::new(&config.model_path).exists() {
             warn!("📥 Model not found, downloading...");
-            crate::download::download_phi4_model(&config.model_path).await?;
+            crate::download::download_phi4_model(
+                &config.model_path,
+                crate::download::Phi4Variant::Phi4Mini,
+                crate::download::QuantMode::Dynamic,
+                None,
+                None,
+            ).await?;
         }
         
         // Initialize ONNX Runtime session
         debug!("🔧 Setting up ONNX Runtime session");
-        let session = Session::builder()?
+        let builder = Session::builder()?
             .with_optimization_level(GraphOptimizationLevel::Level3)?
-            .with_intra_threads(config.num_threads as i16)?
-            .with_model_from_file(&config.model_path)?;
-            
+            .with_intra_threads(config.num_threads as i16)?;
+
+        let (builder, selected_provider) = if config.use_gpu {
+            Self::register_execution_provider(builder, &config.preferred_providers)?
+        } else {
+            (builder, ExecutionProvider::Cpu)
+        };
+        info!("⚙️ Execution provider: {:?}", selected_provider);
+
+        let session = builder.with_model_from_file(&config.model_path)?;
+
         // Load tokenizer
         debug!("📝 Loading tokenizer");
         let tokenizer = Tokenizer::from_file(&config.tokenizer_path)
@@ -47,9 +78,95 @@ impl Phi4MiniEngine {
             session,
             tokenizer,
             config,
+            calibrated_confidence_prior: None,
+            selected_provider,
         })
     }
-    
+
+    /// Register `candidates` (or a platform default order when empty) as
+    /// execution providers to try, in priority order, before CPU.
+    ///
+    /// `ort` tries each registered provider at session-build time and
+    /// transparently falls back to the next (ultimately CPU) if one isn't
+    /// available on this machine, so the highest-priority GPU candidate
+    /// returned here is what was *requested*, not a guaranteed confirmation
+    /// it's the one that engaged — `with_model_from_file`'s own logging is
+    /// the authority on what actually loaded.
+    fn register_execution_provider(
+        builder: SessionBuilder,
+        candidates: &[ExecutionProvider],
+    ) -> Phi4Result<(SessionBuilder, ExecutionProvider)> {
+        let candidates: Vec<ExecutionProvider> = if candidates.is_empty() {
+            Self::default_gpu_providers()
+        } else {
+            candidates.to_vec()
+        };
+
+        let mut built: Vec<(ExecutionProvider, ExecutionProviderDispatch)> = candidates
+            .into_iter()
+            .filter_map(|provider| Self::build_dispatch(provider).map(|dispatch| (provider, dispatch)))
+            .collect();
+
+        if built.is_empty() {
+            warn!("⚠️ No requested execution provider was compiled in, falling back to CPU");
+            return Ok((builder, ExecutionProvider::Cpu));
+        }
+
+        let requested = built[0].0;
+        let dispatches: Vec<ExecutionProviderDispatch> = built.drain(..).map(|(_, dispatch)| dispatch).collect();
+
+        let builder = builder.with_execution_providers(dispatches)?;
+        Ok((builder, requested))
+    }
+
+    /// Build the `ort` dispatch for `provider`, or `None` when the Cargo
+    /// feature gating it isn't enabled in this build
+    fn build_dispatch(provider: ExecutionProvider) -> Option<ExecutionProviderDispatch> {
+        match provider {
+            #[cfg(feature = "cuda")]
+            ExecutionProvider::Cuda => Some(ort::execution_providers::CUDAExecutionProvider::default().build()),
+            #[cfg(not(feature = "cuda"))]
+            ExecutionProvider::Cuda => None,
+
+            #[cfg(feature = "directml")]
+            ExecutionProvider::DirectMl => Some(ort::execution_providers::DirectMLExecutionProvider::default().build()),
+            #[cfg(not(feature = "directml"))]
+            ExecutionProvider::DirectMl => None,
+
+            #[cfg(feature = "coreml")]
+            ExecutionProvider::CoreMl => Some(ort::execution_providers::CoreMLExecutionProvider::default().build()),
+            #[cfg(not(feature = "coreml"))]
+            ExecutionProvider::CoreMl => None,
+
+            ExecutionProvider::Cpu => None,
+        }
+    }
+
+    /// Platform-appropriate GPU provider order tried when
+    /// `Phi4Config.preferred_providers` is left empty
+    fn default_gpu_providers() -> Vec<ExecutionProvider> {
+        #[cfg(target_os = "windows")]
+        return vec![ExecutionProvider::Cuda, ExecutionProvider::DirectMl];
+        #[cfg(target_os = "macos")]
+        return vec![ExecutionProvider::Cuda, ExecutionProvider::CoreMl];
+        #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+        return vec![ExecutionProvider::Cuda];
+    }
+
+    /// Set the confidence prior `parse_cognitive_response` falls back to
+    /// when the model's response doesn't parse as structured JSON
+    pub(crate) fn set_calibrated_confidence_prior(&mut self, prior: f32) {
+        self.calibrated_confidence_prior = Some(prior);
+    }
+
+    /// The execution provider requested for this engine's session (see
+    /// `register_execution_provider` for why this is "requested" rather
+    /// than "confirmed engaged"), so callers (e.g. the Tauri layer) can
+    /// report it instead of assuming `config().use_gpu` held
+    pub fn selected_provider(&self) -> ExecutionProvider {
+        self.selected_provider
+    }
+
     /// Perform cognitive analysis on the given prompt
     /// 
     /// This is the main entry point for Guru's AI-to-AI collaboration.
@@ -80,6 +197,12 @@ impl Phi4MiniEngine {
             input_ids
         };
         
+        // Resolved once here (rather than left for TextGenerator::new to
+        // fill in) so json_constraint below can force the same id
+        // should_stop will actually recognize as EOS, instead of assuming
+        // a fixed one that may not match this tokenizer's export.
+        let eos_token_ids = default_eos_token_ids(&self.tokenizer);
+
         // Create text generator with appropriate config
         let gen_config = GenerationConfig {
             max_new_tokens: 500,
@@ -91,18 +214,39 @@ impl Phi4MiniEngine {
             num_layers: 32,
             num_heads: 32,
             head_dim: 96,
+            num_beams: 1,
+            length_penalty: 1.0,
+            ignore_prompt: true,
+            frequency_penalty: 0.0,
+            presence_penalty: 0.0,
+            n_speculate: 0,
+            output_scores: false,
+            eos_token_ids: eos_token_ids.clone(),
+            // This call site has no chat-turn markers to stop on or a
+            // non-default repetition window to ask for.
+            stop_sequences: Vec::new(),
+            repetition_window: 20,
         };
-        
-        let generator = TextGenerator::new(&self.session, &self.tokenizer, gen_config);
-        
+
+        // The prompt asks for a single top-level JSON object, so force EOS
+        // once it closes instead of burning the full max_new_tokens budget
+        // on every request — see json_constraint for what this does and
+        // doesn't enforce. Forces the first id in `eos_token_ids` (always
+        // non-empty — default_eos_token_ids always returns at least its
+        // [0, 2] fallback) so this doesn't race should_stop's own idea of
+        // what counts as EOS.
+        let forced_eos = eos_token_ids[0];
+        let generator = TextGenerator::new(&self.session, &self.tokenizer, gen_config)
+            .with_prefix_allowed_tokens_fn(json_constraint(&self.tokenizer, forced_eos));
+
         // Generate response using proper text generation with KV cache
         debug!("⚡ Running text generation with KV cache");
         let response = generator.generate(input_ids).await?;
-        
-        debug!("📝 Generated response length: {}", response.len());
-        
+
+        debug!("📝 Generated response length: {}", response.text.len());
+
         // Parse structured cognitive analysis
-        let analysis = self.parse_cognitive_response(&response)?;
+        let analysis = self.parse_cognitive_response(&response.text)?;
         
         Ok(analysis)
     }
@@ -167,13 +311,14 @@ Please provide your analysis in this JSON format:
         
         // Fallback: create analysis from raw response
         warn!("📄 Could not parse JSON, using fallback analysis");
+        let fallback_confidence = self.calibrated_confidence_prior.unwrap_or(0.75);
         Ok(Phi4Analysis {
-            confidence: 0.75, // Default confidence
+            confidence: fallback_confidence,
             mathematical_insights: response.to_string(),
             reasoning_steps: response.lines().map(|s| s.to_string()).collect(),
             pattern_detection: crate::cognitive::PatternDetection {
                 detected_patterns: vec!["text_analysis".to_string()],
-                confidence_scores: vec![0.75],
+                confidence_scores: vec![fallback_confidence],
             },
             architectural_analysis: Some(crate::cognitive::ArchitecturalAnalysis {
                 structure_insights: vec!["Raw text analysis".to_string()],
@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use ort::session::{Session, SessionInputValue, SessionOutputs};
 use ort::value::Value;
 use log::{debug, info};
@@ -10,6 +10,18 @@ pub struct TextGenerator<'a> {
     session: &'a Session,
     tokenizer: &'a tokenizers::Tokenizer,
     config: GenerationConfig,
+
+    /// Optional constrained/grammar-guided decoding hook, called with the
+    /// tokens generated so far before each sampling step. An empty
+    /// (`Vec::new()`) result means "unconstrained" (every token allowed);
+    /// a non-empty one masks every other token's logit to `-inf`. See
+    /// `with_prefix_allowed_tokens_fn` and `json_constraint`.
+    prefix_allowed_tokens_fn: Option<Box<dyn Fn(&[i64]) -> Vec<i64> + 'a>>,
+
+    /// Optional small/fast model used to propose candidate tokens for
+    /// speculative decoding (see `generate_speculative`) when
+    /// `config.n_speculate > 0`. Set via `with_draft_session`.
+    draft_session: Option<&'a Session>,
 }
 
 #[derive(Debug, Clone)]
@@ -23,6 +35,80 @@ pub struct GenerationConfig {
     pub num_layers: usize,
     pub num_heads: usize,
     pub head_dim: usize,
+
+    /// Number of beams to maintain for beam search decoding. `1` (the
+    /// default) disables beam search in favor of the sampling/greedy path.
+    pub num_beams: usize,
+
+    /// Exponent applied to sequence length when ranking finished beams
+    /// (`score / len^length_penalty`, where `score` is a sum of
+    /// log-probabilities and thus <= 0). Any positive value shrinks a
+    /// longer beam's negative score toward zero more than a shorter one's,
+    /// and larger values do so more strongly: `1.0` ranks by per-token
+    /// average, values above it favor longer sequences, and values toward
+    /// `0.0` approach the raw sum, which favors shorter ones. Only used
+    /// when `num_beams > 1`.
+    pub length_penalty: f32,
+
+    /// Whether `generate_stream` should skip emitting the decoded prompt
+    /// as its first chunk. `true` (the default) only streams newly
+    /// generated tokens, matching `generate`'s return value, which never
+    /// echoes the prompt either.
+    pub ignore_prompt: bool,
+
+    /// Additive per-occurrence penalty subtracted from a token's logit for
+    /// every time it's already appeared (`frequency_penalty * count`), on
+    /// top of `repetition_penalty`'s multiplicative one. `0.0` (the
+    /// default) disables it. OpenAI-style knob: scales with how often a
+    /// token recurred, unlike `presence_penalty`'s flat one-time cost.
+    pub frequency_penalty: f32,
+
+    /// Additive one-time penalty subtracted from a token's logit if it's
+    /// appeared at all, regardless of how many times. `0.0` (the default)
+    /// disables it.
+    pub presence_penalty: f32,
+
+    /// Number of tokens to propose per round when a draft session is
+    /// attached via `TextGenerator::with_draft_session`. `0` (the
+    /// default) disables speculative decoding even if a draft session is
+    /// attached. Ignored when `num_beams > 1` (beam search takes
+    /// priority — see `generate`).
+    pub n_speculate: usize,
+
+    /// Whether `generate`/`generate_beam_search`/`generate_speculative`
+    /// should populate `GeneratedOutput::token_logprobs`/`sequence_score`.
+    /// `false` (the default) leaves both `None`: computing a log-softmax
+    /// over the full vocabulary on every step costs something on the
+    /// greedy/sampling path, so it's opt-in there. Beam search already
+    /// computes log-probabilities every step regardless (it needs them to
+    /// rank hypotheses), so this only gates whether that existing work is
+    /// copied out into the result, not whether it happens.
+    pub output_scores: bool,
+
+    /// Token ids `should_stop` treats as end-of-generation. Empty (the
+    /// default) is filled in by `TextGenerator::new` from the tokenizer's
+    /// own special tokens — see `default_eos_token_ids` — rather than
+    /// assuming a fixed id: a hard-coded id is a property of one
+    /// tokenizer.json export, not of "being Phi-4", and a mismatched one
+    /// means generation silently runs to `max_new_tokens` instead of
+    /// stopping. Set this explicitly to override what gets auto-detected.
+    pub eos_token_ids: Vec<i64>,
+
+    /// Strings that end generation when they appear in the decoded
+    /// output, checked via a rolling tail of recently decoded text (see
+    /// `matches_stop_sequence`) and trimmed from the final returned text.
+    /// Empty (the default) disables this. Useful for chat-style prompts
+    /// where the model can run on into a new turn (e.g. `"\n\nUser:"`)
+    /// without ever emitting one of `eos_token_ids`.
+    pub stop_sequences: Vec<String>,
+
+    /// Number of trailing generated tokens the n-gram repetition guard in
+    /// `should_stop` compares (split into two equal halves, checked for
+    /// equality). Rounded down to the nearest even number, so an odd
+    /// value still compares two equal-length halves instead of silently
+    /// never matching. Must be at least `2` to do anything; `20` (the
+    /// previously hard-coded value) is the default.
+    pub repetition_window: usize,
 }
 
 impl Default for GenerationConfig {
@@ -37,92 +123,1029 @@ impl Default for GenerationConfig {
             num_layers: 32,
             num_heads: 32,
             head_dim: 96,
+            num_beams: 1,
+            length_penalty: 1.0,
+            ignore_prompt: true,
+            frequency_penalty: 0.0,
+            presence_penalty: 0.0,
+            n_speculate: 0,
+            output_scores: false,
+            eos_token_ids: Vec::new(),
+            stop_sequences: Vec::new(),
+            repetition_window: 20,
         }
     }
 }
 
-/// Represents the KV cache state between generation steps
+/// The result of a completed (non-streaming) generation call: the decoded
+/// text plus, optionally, enough per-token detail for a caller to rank
+/// several samples, compute perplexity, or apply a confidence threshold
+/// without re-running the model.
+#[derive(Debug, Clone)]
+pub struct GeneratedOutput {
+    /// Decoded text of the generated tokens (prompt excluded, same as the
+    /// `String` `generate` used to return directly).
+    pub text: String,
+
+    /// Token ids generated, in order (prompt excluded).
+    pub token_ids: Vec<i64>,
+
+    /// Log-probability of each chosen token in `token_ids`, aligned
+    /// index-for-index. `None` unless `GenerationConfig::output_scores` is
+    /// `true`.
+    pub token_logprobs: Option<Vec<f32>>,
+
+    /// Sum of `token_logprobs` — the sequence's total log-probability
+    /// under the model (before any length normalization a caller may want
+    /// to apply, e.g. dividing by `token_ids.len()` for perplexity-style
+    /// comparisons across different-length outputs). `None` unless
+    /// `GenerationConfig::output_scores` is `true`.
+    pub sequence_score: Option<f32>,
+}
+
+/// Represents the KV cache state between generation steps: each entry is
+/// the `present.{layer}.{key|value}` output tensor (shape and raw data)
+/// from the previous step's outputs, fed back next step as
+/// `past_key_values.{layer}.{key|value}` so the model only needs to attend
+/// over (not recompute) earlier positions.
+///
+/// The real output shape is kept rather than assumed from
+/// `GenerationConfig.num_heads`/`head_dim`, since an exported KV head count
+/// can differ from the query head count under grouped-query attention.
+#[derive(Clone)]
 pub struct KVCache {
-    cache: HashMap<String, Vec<f32>>, // Store raw cache data
+    cache: HashMap<String, (Vec<i64>, Vec<f32>)>,
+    /// Cached sequence length, shared by every layer's key/value (each
+    /// grows by exactly the number of newly-processed positions per step)
+    past_len: usize,
+}
+
+impl KVCache {
+    fn key_name(layer: usize) -> String {
+        format!("present.{layer}.key")
+    }
+
+    fn value_name(layer: usize) -> String {
+        format!("present.{layer}.value")
+    }
+
+    /// Drop every cached layer's trailing positions down to `new_len`
+    /// along the sequence axis (axis 2 of the `[batch, num_heads,
+    /// seq_len, head_dim]` layout `prepare_inputs` assumes). Used by
+    /// speculative decoding to roll a cache back to only the tokens the
+    /// main model actually accepted, discarding whatever a rejected
+    /// draft tail computed. A no-op if `new_len` isn't shorter than what's
+    /// already cached.
+    fn truncate(&mut self, new_len: usize) {
+        if new_len >= self.past_len {
+            return;
+        }
+        for (shape, data) in self.cache.values_mut() {
+            let batch = shape[0] as usize;
+            let num_heads = shape[1] as usize;
+            let seq_len = shape[2] as usize;
+            let head_dim = shape[3] as usize;
+            if seq_len <= new_len {
+                continue;
+            }
+            let mut truncated = Vec::with_capacity(batch * num_heads * new_len * head_dim);
+            for row in 0..(batch * num_heads) {
+                let row_start = row * seq_len * head_dim;
+                let row_end = row_start + new_len * head_dim;
+                truncated.extend_from_slice(&data[row_start..row_end]);
+            }
+            *data = truncated;
+            shape[2] = new_len as i64;
+        }
+        self.past_len = new_len;
+    }
+}
+
+/// One hypothesis tracked during beam search: the token sequence generated
+/// so far (prompt included), the log-probability `score` accumulated to
+/// reach it, each step's individual log-probability (`logprobs`, same
+/// length as the generated suffix of `ids` — used to populate
+/// `GeneratedOutput::token_logprobs` for the winning beam regardless of
+/// `GenerationConfig::output_scores`, since beam search computes these
+/// anyway to rank hypotheses), and the KV cache built from the forward
+/// pass that produced its last token (`None` only for the very first
+/// step, before any beam has taken a step).
+#[derive(Clone)]
+struct Beam {
+    ids: Vec<i64>,
+    logprobs: Vec<f32>,
+    score: f32,
+    kv_cache: Option<KVCache>,
+}
+
+/// Decodes token ids to text incrementally, one newly generated token at a
+/// time, yielding only the text that's become newly available each push.
+///
+/// Only re-decodes the tokens since the last successful resolution (not
+/// the whole sequence generated so far), since a single BPE/multi-byte
+/// token can decode to a partial or invalid UTF-8 fragment on its own —
+/// `tokenizers` substitutes U+FFFD for bytes it can't yet resolve, which
+/// then becomes valid text once a later token supplies the missing
+/// continuation bytes. Bounding the buffer to the still-unresolved
+/// tokens keeps decode cost roughly linear in total output length instead
+/// of the quadratic cost of re-decoding everything generated so far on
+/// every token.
+///
+/// Can't tell a still-incomplete byte sequence apart from the model
+/// legitimately emitting a literal U+FFFD — in the (rare) latter case this
+/// buffers for the rest of generation and the text only appears via
+/// `flush` at the end, same tradeoff streaming decoders in other
+/// inference stacks accept for the same reason.
+struct IncrementalDecoder {
+    pending_ids: Vec<u32>,
+}
+
+impl IncrementalDecoder {
+    fn new() -> Self {
+        Self { pending_ids: Vec::new() }
+    }
+
+    /// Returns the newly resolved text, or an empty string while `token_id`
+    /// extends a still-unresolved multi-byte sequence.
+    fn push(&mut self, token_id: i64, tokenizer: &tokenizers::Tokenizer) -> Phi4Result<String> {
+        self.pending_ids.push(token_id as u32);
+        let text = tokenizer
+            .decode(&self.pending_ids, true)
+            .map_err(Phi4Error::TokenizerError)?;
+
+        if text.ends_with('\u{FFFD}') {
+            return Ok(String::new());
+        }
+        self.pending_ids.clear();
+        Ok(text)
+    }
+
+    /// Decode whatever's left in the buffer at the end of generation, so a
+    /// trailing unresolved sequence truncated by `max_new_tokens` or
+    /// `should_stop` (rather than a later token resolving it) is still
+    /// emitted instead of silently dropped.
+    fn flush(&self, tokenizer: &tokenizers::Tokenizer) -> Phi4Result<String> {
+        if self.pending_ids.is_empty() {
+            return Ok(String::new());
+        }
+        tokenizer.decode(&self.pending_ids, true).map_err(Phi4Error::TokenizerError)
+    }
+}
+
+/// Convert raw logits into log-probabilities. Beam search accumulates
+/// scores across many steps, so log-probabilities (summed) are used
+/// instead of probabilities (which would need to be multiplied, and
+/// underflow over a long sequence).
+fn log_softmax(logits: &[f32]) -> Vec<f32> {
+    let max_logit = logits.iter().fold(f32::NEG_INFINITY, |a, &b| a.max(b));
+    let log_sum_exp = logits.iter().map(|&x| (x - max_logit).exp()).sum::<f32>().ln();
+    logits.iter().map(|&x| x - max_logit - log_sum_exp).collect()
+}
+
+/// Index of the highest logit — greedy decoding's token choice. Shared
+/// by `select_next_token`'s greedy branch and speculative decoding's
+/// draft phase (which is always greedy, regardless of `do_sample`) so
+/// the two can't drift apart on tie-breaking/NaN handling.
+fn argmax(logits: &[f32]) -> i64 {
+    logits
+        .iter()
+        .enumerate()
+        .max_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap())
+        .map(|(idx, _)| idx as i64)
+        .unwrap()
+}
+
+/// Look up whichever of Phi-4's common end-of-text/end-of-turn special
+/// tokens are actually present in `tokenizer`, used by `TextGenerator::new`
+/// to fill in `GenerationConfig::eos_token_ids` when a caller leaves it
+/// empty. Exported tokenizer.json files vary in which of these they
+/// define and under what id, so looking them up beats assuming a fixed id
+/// that happened to be right for one export.
+///
+/// Falls back to `[0, 2]` — the ids this generator hard-coded before this
+/// lookup existed — only if none of the candidates resolve, so a
+/// tokenizer this can't recognize doesn't regress to "never stops".
+///
+/// `pub(crate)` so callers that need to force one of these ids elsewhere
+/// (e.g. `json_constraint`'s forced-EOS token) can resolve the same id
+/// `should_stop` will actually recognize, instead of hard-coding one that
+/// may not match this tokenizer's export.
+pub(crate) fn default_eos_token_ids(tokenizer: &tokenizers::Tokenizer) -> Vec<i64> {
+    const CANDIDATES: &[&str] = &["<|endoftext|>", "<|end|>", "</s>"];
+
+    let found: Vec<i64> = CANDIDATES
+        .iter()
+        .filter_map(|&token| tokenizer.token_to_id(token))
+        .map(|id| id as i64)
+        .collect();
+
+    if found.is_empty() {
+        vec![0, 2]
+    } else {
+        found
+    }
+}
+
+/// How many trailing tokens `matches_stop_sequence` decodes to check for
+/// configured `GenerationConfig::stop_sequences`. Bounded rather than the
+/// whole sequence so the check's cost doesn't grow with how long
+/// generation has already run — stop markers like `"\n\nUser:"` are
+/// always short, so a small tail comfortably covers one even split across
+/// several BPE tokens.
+const STOP_SEQUENCE_TAIL_TOKENS: usize = 32;
+
+/// Build a `prefix_allowed_tokens_fn` (see `TextGenerator::
+/// with_prefix_allowed_tokens_fn`) that forces `eos_token_id` once the
+/// top-level JSON value generated so far has closed, replacing the old
+/// `should_stop` heuristic of checking whether the last few decoded
+/// characters merely *contain* `}` and a newline — that could fire inside
+/// a nested object or a string value containing a literal `}`. This
+/// tracks brace/bracket depth while skipping over string contents (so a
+/// `}` inside a quoted string doesn't count), which catches exactly what
+/// the old heuristic was trying to approximate: knowing reliably when the
+/// top-level structure has closed.
+///
+/// `apply_prefix_allowed_tokens_constraint` calls every
+/// `prefix_allowed_tokens_fn` with only the generated-so-far suffix (the
+/// prompt is never included), same convention `should_stop` already uses,
+/// so a one-shot JSON example in the prompt itself can't trip this early.
+///
+/// This does not attempt full per-token JSON grammar enforcement while
+/// the structure is still open (e.g. rejecting a token that would start
+/// an invalid key) — masking every vocab token against a JSON grammar at
+/// every step is a much larger automaton-over-the-vocabulary undertaking;
+/// this covers termination, which is the concrete problem the replaced
+/// heuristic existed for.
+///
+/// Re-decodes the full generated-so-far suffix on every call rather than
+/// tracking depth incrementally — unlike `IncrementalDecoder`, the same
+/// closure here is called for several diverging beam-search hypotheses
+/// (not one strictly-growing sequence), so a single running depth/string
+/// state couldn't be shared safely across calls. Cost is bounded by
+/// `max_new_tokens`, not the prompt length, since the caller excludes the
+/// prompt before this ever sees it.
+pub fn json_constraint<'a>(
+    tokenizer: &'a tokenizers::Tokenizer,
+    eos_token_id: i64,
+) -> impl Fn(&[i64]) -> Vec<i64> + 'a {
+    move |generated_ids: &[i64]| {
+        let ids: Vec<u32> = generated_ids.iter().map(|&id| id as u32).collect();
+        let Ok(text) = tokenizer.decode(&ids, true) else {
+            return Vec::new();
+        };
+
+        // Tracks expected closers rather than a bare depth counter, so a
+        // hallucinated mismatched close (e.g. an array opened with `[`
+        // but closed with `}`) doesn't get counted as closing anything —
+        // small models produce structurally invalid JSON often enough
+        // that this is worth guarding against.
+        let mut closers: Vec<char> = Vec::new();
+        let mut opened = false;
+        let mut in_string = false;
+        let mut escaped = false;
+
+        for ch in text.chars() {
+            if in_string {
+                if escaped {
+                    escaped = false;
+                } else if ch == '\\' {
+                    escaped = true;
+                } else if ch == '"' {
+                    in_string = false;
+                }
+                continue;
+            }
+            match ch {
+                '"' => in_string = true,
+                '{' => {
+                    closers.push('}');
+                    opened = true;
+                }
+                '[' => {
+                    closers.push(']');
+                    opened = true;
+                }
+                '}' | ']' => {
+                    if closers.last() == Some(&ch) {
+                        closers.pop();
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        if opened && closers.is_empty() {
+            vec![eos_token_id]
+        } else {
+            Vec::new()
+        }
+    }
 }
 
 impl<'a> TextGenerator<'a> {
     pub fn new(
         session: &'a Session,
         tokenizer: &'a tokenizers::Tokenizer,
-        config: GenerationConfig,
+        mut config: GenerationConfig,
     ) -> Self {
+        if config.eos_token_ids.is_empty() {
+            config.eos_token_ids = default_eos_token_ids(tokenizer);
+        }
         Self {
             session,
             tokenizer,
             config,
+            prefix_allowed_tokens_fn: None,
+            draft_session: None,
         }
     }
 
+    /// Constrain each step's sampling to only the token ids `f` returns
+    /// for the sequence generated so far (an empty result means
+    /// "unconstrained" rather than "nothing allowed", since masking the
+    /// entire vocab would make generation stuck). See `json_constraint`
+    /// for a built-in grammar-guided implementation.
+    pub fn with_prefix_allowed_tokens_fn(
+        mut self,
+        f: impl Fn(&[i64]) -> Vec<i64> + 'a,
+    ) -> Self {
+        self.prefix_allowed_tokens_fn = Some(Box::new(f));
+        self
+    }
+
+    /// Attach a small/fast model whose proposals `generate`/`generate_stream`
+    /// verify against the main model once `config.n_speculate > 0` — see
+    /// `generate_speculative`. Has no effect while `n_speculate` is `0`.
+    ///
+    /// `draft_session` must export the same `present.{layer}.{key|value}`
+    /// shape as the main session for every layer in `config.num_layers`
+    /// (i.e. share `num_layers`/`num_heads`/`head_dim` with the main
+    /// model) — `prepare_inputs`/`extract_kv_cache` read `self.config`
+    /// regardless of which session they're building inputs for or
+    /// reading outputs from, since `TextGenerator` only has one
+    /// `GenerationConfig`. A draft model with a different layer count
+    /// fails on its first step instead of silently misbehaving.
+    pub fn with_draft_session(mut self, draft_session: &'a Session) -> Self {
+        self.draft_session = Some(draft_session);
+        self
+    }
+
     /// Generate text completion for the given input IDs
-    pub async fn generate(&self, input_ids: Vec<i64>) -> Phi4Result<String> {
+    pub async fn generate(&self, input_ids: Vec<i64>) -> Phi4Result<GeneratedOutput> {
+        if self.config.num_beams > 1 {
+            return self.generate_beam_search(input_ids).await;
+        }
+        if self.config.n_speculate > 0 {
+            if self.draft_session.is_some() {
+                return self.generate_speculative(input_ids).await;
+            }
+            log::warn!(
+                "n_speculate > 0 but no draft session attached via with_draft_session; falling back to standard generation"
+            );
+        }
+        self.generate_standard(input_ids).await
+    }
+
+    /// The ordinary one-token-at-a-time greedy/sampling generation loop,
+    /// with no beam search or speculative decoding. Factored out of
+    /// `generate` so `generate_speculative` can fall back to it directly
+    /// (bypassing `generate`'s own dispatch, which would otherwise route
+    /// straight back into speculative decoding and recurse forever).
+    async fn generate_standard(&self, input_ids: Vec<i64>) -> Phi4Result<GeneratedOutput> {
         info!("🚀 Starting text generation for {} input tokens", input_ids.len());
-        
+
+        let prompt_len = input_ids.len();
         let mut current_ids = input_ids;
         let mut generated_tokens = Vec::new();
+        let mut generated_logprobs: Vec<f32> = Vec::new();
         let mut kv_cache: Option<KVCache> = None;
-        
+
         // Generation loop
         for step in 0..self.config.max_new_tokens {
-            // Prepare inputs for this step
-            let inputs = self.prepare_inputs(&current_ids, &kv_cache)?;
-            
-            // Run inference
-            let outputs = self.session.run(inputs)?;
-            
-            // Extract logits and process
-            let next_token = self.process_outputs(&outputs, &current_ids)?;
-            
+            let (next_token, logprob, next_kv_cache) = self.generation_step(&current_ids, prompt_len, kv_cache.take())?;
+
             // Check stopping conditions
             if self.should_stop(next_token, &generated_tokens) {
                 debug!("🛑 Stopping generation at step {}", step);
                 break;
             }
-            
+
             // Update state
+            let prior_len = generated_tokens.len();
             generated_tokens.push(next_token);
+            if let Some(lp) = logprob {
+                generated_logprobs.push(lp);
+            }
             current_ids.push(next_token);
-            
-            // Update KV cache from outputs
-            kv_cache = Some(self.extract_kv_cache(&outputs)?);
-            
+            kv_cache = Some(next_kv_cache);
+
+            if self.matches_stop_sequence(next_token, &generated_tokens[..prior_len]) {
+                debug!("🛑 Stop sequence matched, stopping generation at step {}", step);
+                break;
+            }
+
             // Progress indicator
             if step % 20 == 0 && step > 0 {
                 debug!("Generated {} tokens", step);
             }
         }
-        
+
         // Decode generated tokens to text
         let generated_ids: Vec<u32> = generated_tokens.iter().map(|&id| id as u32).collect();
-        let text = self.tokenizer
+        let mut text = self.tokenizer
             .decode(&generated_ids, true)
             .map_err(Phi4Error::TokenizerError)?;
-        
+        self.trim_stop_sequences(&mut text);
+
         info!("✅ Generated {} tokens", generated_tokens.len());
+
+        let (token_logprobs, sequence_score) = if self.config.output_scores {
+            let score: f32 = generated_logprobs.iter().sum();
+            (Some(generated_logprobs), Some(score))
+        } else {
+            (None, None)
+        };
+
+        Ok(GeneratedOutput { text, token_ids: generated_tokens, token_logprobs, sequence_score })
+    }
+
+    /// Streaming variant of `generate`: calls `on_token` with each text
+    /// fragment as it becomes available instead of only returning the full
+    /// response once generation finishes, so an interactive UI can render
+    /// output as it's produced. Still returns the complete text at the end,
+    /// same as `generate`.
+    ///
+    /// Runs in the same task as the caller rather than a spawned
+    /// background task — `TextGenerator` only borrows its session and
+    /// tokenizer, and spawning would require `'static` ownership of both —
+    /// so `on_token` is called synchronously partway through this call. A
+    /// caller already driving its own event loop (e.g. a Tauri command
+    /// emitting a window event per call) still renders tokens
+    /// incrementally rather than only at the end.
+    ///
+    /// Beam search keeps several hypotheses alive at once with no single
+    /// "current" sequence to stream until one wins, so this isn't
+    /// supported when `num_beams > 1`.
+    ///
+    /// `stop_sequences` only withholds the single token that completes a
+    /// match from `on_token` (see the check ahead of `decoder.push`
+    /// below) — any earlier part of the matched string spanning prior
+    /// tokens has already been streamed by the time the match completes,
+    /// even though `trim_stop_sequences` removes all of it from the final
+    /// returned text. Buffering output by `STOP_SEQUENCE_TAIL_TOKENS` to
+    /// close that gap would add that much latency to every streamed
+    /// token, not just ones near a stop sequence, so callers that can't
+    /// tolerate a stop marker briefly flashing in a live UI should prefer
+    /// non-streaming `generate` instead.
+    pub async fn generate_stream(
+        &self,
+        input_ids: Vec<i64>,
+        mut on_token: impl FnMut(&str),
+    ) -> Phi4Result<String> {
+        if self.config.num_beams > 1 {
+            return Err(Phi4Error::InvalidInput(
+                "generate_stream does not support num_beams > 1".to_string(),
+            ));
+        }
+
+        if self.config.n_speculate > 0 && self.draft_session.is_some() {
+            log::warn!(
+                "generate_stream does not support speculative decoding yet; ignoring n_speculate and streaming token-by-token from the main model"
+            );
+        }
+
+        if self.config.output_scores {
+            log::warn!(
+                "generate_stream does not return per-token scores; ignoring output_scores (use generate instead if you need GeneratedOutput)"
+            );
+        }
+
+        info!("🚀 Starting streaming text generation for {} input tokens", input_ids.len());
+
+        let mut decoder = IncrementalDecoder::new();
+
+        if !self.config.ignore_prompt {
+            let prompt_ids: Vec<u32> = input_ids.iter().map(|&id| id as u32).collect();
+            let prompt_text = self.tokenizer
+                .decode(&prompt_ids, true)
+                .map_err(Phi4Error::TokenizerError)?;
+            if !prompt_text.is_empty() {
+                on_token(&prompt_text);
+            }
+        }
+
+        let prompt_len = input_ids.len();
+        let mut current_ids = input_ids;
+        let mut generated_tokens = Vec::new();
+        let mut kv_cache: Option<KVCache> = None;
+
+        for step in 0..self.config.max_new_tokens {
+            let (next_token, _logprob, next_kv_cache) = self.generation_step(&current_ids, prompt_len, kv_cache.take())?;
+
+            if self.should_stop(next_token, &generated_tokens) {
+                debug!("🛑 Stopping generation at step {}", step);
+                break;
+            }
+
+            let prior_len = generated_tokens.len();
+            generated_tokens.push(next_token);
+            current_ids.push(next_token);
+            kv_cache = Some(next_kv_cache);
+
+            // Checked before decoder.push/on_token below: the token
+            // completing a stop sequence match must never reach the
+            // caller, or a chat-style stop marker (e.g. "\n\nUser:")
+            // would get rendered to a live streaming UI right as
+            // generation stops because of it.
+            if self.matches_stop_sequence(next_token, &generated_tokens[..prior_len]) {
+                debug!("🛑 Stop sequence matched, stopping generation at step {}", step);
+                break;
+            }
+
+            let fragment = decoder.push(next_token, self.tokenizer)?;
+            if !fragment.is_empty() {
+                on_token(&fragment);
+            }
+
+            if step % 20 == 0 && step > 0 {
+                debug!("Generated {} tokens", step);
+            }
+        }
+
+        // Generation can end (max_new_tokens, should_stop) while the
+        // decoder still has an unresolved trailing sequence buffered —
+        // emit it now rather than losing it
+        let trailing = decoder.flush(self.tokenizer)?;
+        if !trailing.is_empty() {
+            on_token(&trailing);
+        }
+
+        let generated_ids: Vec<u32> = generated_tokens.iter().map(|&id| id as u32).collect();
+        let mut text = self.tokenizer
+            .decode(&generated_ids, true)
+            .map_err(Phi4Error::TokenizerError)?;
+        self.trim_stop_sequences(&mut text);
+
+        info!("✅ Generated {} tokens (streamed)", generated_tokens.len());
         Ok(text)
     }
 
-    /// Prepare model inputs for the current generation step
+    /// Run one autoregressive decoding step: build inputs from
+    /// `current_ids` and `kv_cache`, run the session, and pick the next
+    /// token. Shared by `generate` and `generate_stream` so the KV-cache
+    /// plumbing (step length / past length bookkeeping) only lives in one
+    /// place.
+    fn generation_step(
+        &self,
+        current_ids: &[i64],
+        prompt_len: usize,
+        kv_cache: Option<KVCache>,
+    ) -> Phi4Result<(i64, Option<f32>, KVCache)> {
+        // The number of positions this step's forward pass actually
+        // processes: the whole prompt on the first step (no cache yet),
+        // just the newest token on every step after (prior positions
+        // are already represented in the KV cache)
+        let step_input_len = match &kv_cache {
+            None => current_ids.len(),
+            Some(_) => 1,
+        };
+        let past_len_before = kv_cache.as_ref().map_or(0, |c| c.past_len);
+
+        // Takes ownership of kv_cache (rather than cloning its per-layer
+        // tensors) since it's about to be replaced wholesale by this
+        // step's extract_kv_cache below regardless.
+        let inputs = self.prepare_inputs(current_ids, kv_cache, step_input_len)?;
+        let outputs = self.session.run(inputs)?;
+
+        let (next_token, logprob) = self.process_outputs(&outputs, current_ids, prompt_len)?;
+
+        // `present.*` already contains the concatenation of whatever past
+        // was fed in plus the positions just processed
+        let next_kv_cache = self.extract_kv_cache(&outputs, past_len_before + step_input_len)?;
+
+        Ok((next_token, logprob, next_kv_cache))
+    }
+
+    /// Beam search decoding: maintains `num_beams` hypotheses, each with its
+    /// own running KV cache and cumulative log-probability. Each step,
+    /// every active beam is expanded individually (one `session.run` per
+    /// beam, not a batched forward pass — consistent with the rest of this
+    /// generator's batch_size=1-only input construction) over its top
+    /// `num_beams * 2` next-token candidates by log-probability, to tolerate
+    /// some of them emitting EOS. Any candidate that does is moved into the
+    /// finished set with a length-penalty-adjusted score
+    /// (`score / len^length_penalty`); the rest compete for the next
+    /// round's `num_beams` slots. Stops once enough beams have finished or
+    /// `max_new_tokens` is reached, then returns the highest-scoring
+    /// completed sequence.
+    async fn generate_beam_search(&self, input_ids: Vec<i64>) -> Phi4Result<GeneratedOutput> {
+        info!(
+            "🚀 Starting beam search generation ({} beams) for {} input tokens",
+            self.config.num_beams,
+            input_ids.len()
+        );
+
+        let prompt_len = input_ids.len();
+        let mut beams = vec![Beam { ids: input_ids, logprobs: Vec::new(), score: 0.0, kv_cache: None }];
+        let mut finished: Vec<Beam> = Vec::new();
+
+        for step in 0..self.config.max_new_tokens {
+            if finished.len() >= self.config.num_beams {
+                break;
+            }
+
+            // Defer building child Beams (and cloning their parent's KV
+            // cache) until after pruning to the surviving num_beams*2
+            // candidates below — most of the num_beams*2 per-beam
+            // candidates computed here don't survive, so cloning a cache
+            // for all of them up front would be wasted work.
+            let mut parents: Vec<(Vec<i64>, Vec<f32>, KVCache)> = Vec::with_capacity(beams.len());
+            // (parent_index, token_id, this step's own log-probability,
+            // cumulative score through this candidate)
+            let mut all_candidates: Vec<(usize, i64, f32, f32)> = Vec::new();
+
+            for beam in beams {
+                let step_input_len = match &beam.kv_cache {
+                    None => beam.ids.len(),
+                    Some(_) => 1,
+                };
+                let past_len_before = beam.kv_cache.as_ref().map_or(0, |c| c.past_len);
+                let parent_score = beam.score;
+
+                let inputs = self.prepare_inputs(&beam.ids, beam.kv_cache, step_input_len)?;
+                let outputs = self.session.run(inputs)?;
+
+                let mut logits = self.extract_last_token_logits(&outputs)?;
+                self.apply_repetition_penalty(&mut logits, &beam.ids);
+                self.apply_frequency_presence_penalty(&mut logits, &beam.ids);
+                self.apply_prefix_allowed_tokens_constraint(&mut logits, &beam.ids[prompt_len..]);
+                let log_probs = log_softmax(&logits);
+                let next_kv_cache = self.extract_kv_cache(&outputs, past_len_before + step_input_len)?;
+
+                let mut indexed: Vec<(usize, f32)> = log_probs.into_iter().enumerate().collect();
+                indexed.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap());
+
+                let parent_index = parents.len();
+                for &(token_id, log_prob) in indexed.iter().take(self.config.num_beams * 2) {
+                    all_candidates.push((parent_index, token_id as i64, log_prob, parent_score + log_prob));
+                }
+                parents.push((beam.ids, beam.logprobs, next_kv_cache));
+            }
+
+            all_candidates.sort_by(|a, b| b.3.partial_cmp(&a.3).unwrap());
+            all_candidates.truncate(self.config.num_beams * 2);
+
+            let mut next_beams = Vec::new();
+            for (parent_index, token_id, log_prob, score) in all_candidates {
+                let (parent_ids, parent_logprobs, parent_cache) = &parents[parent_index];
+
+                // should_stop also covers EOS and repetition, same as the
+                // greedy/sampling path — called with the parent's
+                // already-generated suffix (not yet including `token_id`),
+                // matching how generate() calls it. Like generate(), the
+                // stop-triggering token itself is never appended to the
+                // output. JSON termination is no longer should_stop's
+                // job — see json_constraint.
+                if self.should_stop(token_id, &parent_ids[prompt_len..]) {
+                    finished.push(Beam { ids: parent_ids.clone(), logprobs: parent_logprobs.clone(), score, kv_cache: None });
+                } else if self.matches_stop_sequence(token_id, &parent_ids[prompt_len..]) {
+                    // Unlike should_stop's EOS case, the stop-triggering
+                    // token is appended here so the matched string is
+                    // actually present in the decoded text for
+                    // trim_stop_sequences to remove below.
+                    let mut ids = parent_ids.clone();
+                    ids.push(token_id);
+                    let mut logprobs = parent_logprobs.clone();
+                    logprobs.push(log_prob);
+                    finished.push(Beam { ids, logprobs, score, kv_cache: None });
+                } else if next_beams.len() < self.config.num_beams {
+                    let mut ids = parent_ids.clone();
+                    ids.push(token_id);
+                    let mut logprobs = parent_logprobs.clone();
+                    logprobs.push(log_prob);
+                    next_beams.push(Beam { ids, logprobs, score, kv_cache: Some(parent_cache.clone()) });
+                }
+            }
+
+            beams = next_beams;
+            if beams.is_empty() {
+                break;
+            }
+
+            if step % 20 == 0 && step > 0 {
+                debug!("Beam search at step {step}, {} beams finished", finished.len());
+            }
+        }
+
+        // Whatever didn't finish by max_new_tokens still competes as-is
+        finished.extend(beams);
+
+        let best = finished
+            .into_iter()
+            .max_by(|a, b| {
+                let score_a = length_penalized_score(a.score, a.ids.len() - prompt_len, self.config.length_penalty);
+                let score_b = length_penalized_score(b.score, b.ids.len() - prompt_len, self.config.length_penalty);
+                score_a.partial_cmp(&score_b).unwrap()
+            })
+            .ok_or_else(|| Phi4Error::InferenceFailed("Beam search produced no candidates".to_string()))?;
+
+        let generated_ids: Vec<u32> = best.ids[prompt_len..].iter().map(|&id| id as u32).collect();
+        let mut text = self.tokenizer
+            .decode(&generated_ids, true)
+            .map_err(Phi4Error::TokenizerError)?;
+        self.trim_stop_sequences(&mut text);
+
+        info!("✅ Beam search generated {} tokens", generated_ids.len());
+
+        let (token_logprobs, sequence_score) = if self.config.output_scores {
+            // Summed from best.logprobs rather than reusing best.score: a
+            // beam that finished on EOS has that token's log-probability in
+            // its ranking score but not in its (EOS-free) token list.
+            let score: f32 = best.logprobs.iter().sum();
+            (Some(best.logprobs), Some(score))
+        } else {
+            (None, None)
+        };
+
+        Ok(GeneratedOutput { text, token_ids: best.ids[prompt_len..].to_vec(), token_logprobs, sequence_score })
+    }
+
+    /// Speculative decoding: propose `config.n_speculate` tokens with the
+    /// cheap `draft_session` model, then verify all of them in a single
+    /// forward pass of the main model, accepting the longest leading run
+    /// where the main model's own choice (computed exactly as
+    /// `process_outputs` would — greedy argmax or a regular sample,
+    /// matching `config.do_sample`) agrees with the draft. On the first
+    /// disagreement the main model's own token is taken instead and the
+    /// rest of the draft is discarded. Because the main model's forward
+    /// pass verifies a whole block at once, several tokens can be
+    /// accepted per expensive step instead of one — the draft only
+    /// changes how many main-model forward passes that takes, not what
+    /// gets produced (under `do_sample == false`; see below for the
+    /// sampling case).
+    ///
+    /// Both the main and draft KV caches are kept deliberately one token
+    /// "behind" `current_ids`: each verification pass re-feeds the most
+    /// recently accepted token alongside the new draft tokens, so the
+    /// pass's first output position is already the right distribution to
+    /// judge the first draft token, without a separate priming forward
+    /// pass every round. Whichever prefix isn't accepted is trimmed back
+    /// out of both caches with `KVCache::truncate`.
+    ///
+    /// Two simplifications relative to the reference algorithm: this
+    /// doesn't sample the "bonus" token past a fully-accepted draft block
+    /// (the freebie the main model's own verification pass could also
+    /// provide for free) — it simply starts the next round from there
+    /// instead, costing one extra forward pass over the full win; and
+    /// under `do_sample == true`, accept/reject is a plain token-identity
+    /// comparison rather than the paper's rejection-sampling test against
+    /// the probability ratio, so sampling-mode output isn't guaranteed
+    /// distributionally identical to non-speculative sampling the way
+    /// greedy mode is — acceptable here since the goal is matching this
+    /// request's literal spec (agreement between the two models' own
+    /// choices), not the full algorithm.
+    async fn generate_speculative(&self, input_ids: Vec<i64>) -> Phi4Result<GeneratedOutput> {
+        let draft_session = self.draft_session.expect(
+            "generate_speculative requires a draft session; callers must check config.n_speculate > 0 && draft_session.is_some() first",
+        );
+
+        info!(
+            "🚀 Starting speculative decoding (n_speculate={}) for {} input tokens",
+            self.config.n_speculate,
+            input_ids.len()
+        );
+
+        // The prefill step below holds back the prompt's last token so
+        // the "cache lags one token behind current_ids" invariant holds
+        // from the first round — with a 1-token (or empty) prompt that
+        // would feed a zero-length input, which the model can't accept.
+        // Speculative decoding has nothing meaningful to propose over
+        // such a short prompt anyway, so just skip straight to the
+        // ordinary loop.
+        if input_ids.len() < 2 {
+            log::warn!("Prompt too short for speculative decoding; falling back to standard generation");
+            return self.generate_standard(input_ids).await;
+        }
+
+        let prompt_len = input_ids.len();
+        let mut current_ids = input_ids;
+        let mut generated_tokens: Vec<i64> = Vec::new();
+        let mut generated_logprobs: Vec<f32> = Vec::new();
+
+        let mut accepted_total: usize = 0;
+        let mut proposed_total: usize = 0;
+
+        // Prime both caches over everything but the prompt's last token,
+        // so the "cache lags one token behind current_ids" invariant
+        // holds from the very first round.
+        //
+        // These two prefill passes don't depend on each other and could in
+        // principle run concurrently, but `ort::Session::run` is a
+        // blocking CPU call and `session`/`draft_session` are borrowed
+        // (not owned), so there's no `'static` handle to hand to
+        // `tokio::task::spawn_blocking` — the same constraint
+        // `generate_stream`'s doc comment describes for why it doesn't
+        // spawn either. Running them sequentially costs one extra prefill
+        // latency per `generate_speculative` call, not per round.
+        let prefill_len = current_ids.len() - 1;
+        let main_inputs = self.prepare_inputs(&current_ids[..prefill_len], None, prefill_len)?;
+        let main_outputs = self.session.run(main_inputs)?;
+        let mut main_cache = Some(self.extract_kv_cache(&main_outputs, prefill_len)?);
+
+        let draft_inputs = self.prepare_inputs(&current_ids[..prefill_len], None, prefill_len)?;
+        let draft_outputs = draft_session.run(draft_inputs)?;
+        let mut draft_cache = Some(self.extract_kv_cache(&draft_outputs, prefill_len)?);
+
+        let mut round: usize = 0;
+        'outer: while generated_tokens.len() < self.config.max_new_tokens {
+            round += 1;
+            let n_draft = self.config.n_speculate.min(self.config.max_new_tokens - generated_tokens.len());
+            if n_draft == 0 {
+                break;
+            }
+            let len_before = current_ids.len();
+
+            // Draft phase: propose up to n_draft tokens, one cheap
+            // autoregressive step at a time. Drafts are greedy and
+            // unpenalized — they're only ever proposals the main model
+            // verifies, so their own sampling policy can't change the
+            // output distribution.
+            let mut draft_tokens: Vec<i64> = Vec::new();
+            let mut draft_ids = current_ids.clone();
+            let mut draft_step_cache = draft_cache.take();
+            for _ in 0..n_draft {
+                let past_len_before = draft_step_cache.as_ref().map_or(0, |c| c.past_len);
+                let inputs = self.prepare_inputs(&draft_ids, draft_step_cache.take(), 1)?;
+                let outputs = draft_session.run(inputs)?;
+                let logits = self.extract_last_token_logits(&outputs)?;
+                let next = argmax(&logits);
+                draft_step_cache = Some(self.extract_kv_cache(&outputs, past_len_before + 1)?);
+                draft_ids.push(next);
+                draft_tokens.push(next);
+                if self.config.eos_token_ids.contains(&next) {
+                    break;
+                }
+            }
+
+            // Verify phase: one main-model forward pass over the
+            // held-back last accepted token plus the whole draft block.
+            let mut verify_ids = current_ids.clone();
+            verify_ids.extend_from_slice(&draft_tokens);
+            let verify_len = 1 + draft_tokens.len();
+            let main_past_len_before = main_cache.as_ref().map_or(0, |c| c.past_len);
+            let main_inputs = self.prepare_inputs(&verify_ids, main_cache.take(), verify_len)?;
+            let main_outputs = self.session.run(main_inputs)?;
+            let mut position_logits = self.extract_all_position_logits(&main_outputs)?;
+            let mut new_main_cache = self.extract_kv_cache(&main_outputs, main_past_len_before + verify_len)?;
+
+            let mut accepted_count = 0;
+            let mut accepted_logprobs: Vec<f32> = Vec::new();
+            let mut override_token: Option<i64> = None;
+            let mut override_logprob: Option<f32> = None;
+            let mut verify_prior = current_ids.clone();
+            for (i, draft_token) in draft_tokens.iter().enumerate() {
+                // Mutated in place rather than cloned — position_logits
+                // is local and never read again after this loop.
+                let (main_choice, logprob) = self.select_next_token(&mut position_logits[i], &verify_prior, prompt_len)?;
+
+                if main_choice == *draft_token {
+                    accepted_count += 1;
+                    if let Some(lp) = logprob {
+                        accepted_logprobs.push(lp);
+                    }
+                    verify_prior.push(*draft_token);
+                } else {
+                    override_token = Some(main_choice);
+                    override_logprob = logprob;
+                    break;
+                }
+            }
+            proposed_total += draft_tokens.len();
+            accepted_total += accepted_count;
+
+            let new_tokens = accepted_tokens(&draft_tokens, accepted_count, override_token);
+            let mut new_logprobs: Vec<f32> = accepted_logprobs;
+            if override_token.is_some() {
+                if let Some(lp) = override_logprob {
+                    new_logprobs.push(lp);
+                }
+            }
+
+            // Apply the same stop semantics (EOS, repetition) every other
+            // path uses: the stop-triggering token is never appended.
+            //
+            // `new_logprobs` is only ever populated (and kept in lockstep
+            // with `new_tokens`) when `config.output_scores` is set — see
+            // `select_next_token` — so indexing it here is safe exactly
+            // when there's something to push.
+            let mut accepted_final = 0;
+            let mut stop_sequence_hit = false;
+            for (i, token) in new_tokens.iter().enumerate() {
+                if self.should_stop(*token, &generated_tokens) {
+                    break;
+                }
+                generated_tokens.push(*token);
+                if self.config.output_scores {
+                    generated_logprobs.push(new_logprobs[i]);
+                }
+                current_ids.push(*token);
+                accepted_final += 1;
+
+                // Unlike should_stop's EOS case, the token above is
+                // already pushed before this check so the matched string
+                // is actually present in the decoded text for
+                // trim_stop_sequences to remove later.
+                if self.matches_stop_sequence(*token, &generated_tokens[..generated_tokens.len() - 1]) {
+                    stop_sequence_hit = true;
+                    break;
+                }
+            }
+
+            if accepted_final < new_tokens.len() {
+                if stop_sequence_hit {
+                    debug!("🛑 Stop sequence matched, stopping speculative generation");
+                } else {
+                    debug!("🛑 Stopping speculative generation");
+                }
+                break 'outer;
+            }
+
+            // Roll both caches forward to cover everything but the
+            // newest accepted token, restoring the lag-by-one invariant
+            // for the next round.
+            let target_len = speculative_cache_rollback_len(len_before, accepted_final);
+            new_main_cache.truncate(target_len);
+            main_cache = Some(new_main_cache);
+
+            let mut new_draft_cache = draft_step_cache.take().unwrap();
+            new_draft_cache.truncate(target_len);
+            draft_cache = Some(new_draft_cache);
+
+            if round % 20 == 0 {
+                debug!(
+                    "Speculative decoding at {} tokens ({} accepted / {} proposed so far)",
+                    generated_tokens.len(),
+                    accepted_total,
+                    proposed_total
+                );
+            }
+        }
+
+        let acceptance_rate = if proposed_total > 0 {
+            accepted_total as f32 / proposed_total as f32
+        } else {
+            0.0
+        };
+        info!(
+            "✅ Speculative decoding generated {} tokens ({} accepted / {} proposed, {:.1}% acceptance rate)",
+            generated_tokens.len(),
+            accepted_total,
+            proposed_total,
+            acceptance_rate * 100.0
+        );
+
+        let generated_ids: Vec<u32> = generated_tokens.iter().map(|&id| id as u32).collect();
+        let mut text = self.tokenizer
+            .decode(&generated_ids, true)
+            .map_err(Phi4Error::TokenizerError)?;
+        self.trim_stop_sequences(&mut text);
+
+        let (token_logprobs, sequence_score) = if self.config.output_scores {
+            let score: f32 = generated_logprobs.iter().sum();
+            (Some(generated_logprobs), Some(score))
+        } else {
+            (None, None)
+        };
+
+        Ok(GeneratedOutput { text, token_ids: generated_tokens, token_logprobs, sequence_score })
+    }
+
+    /// Prepare model inputs for the current generation step. Takes
+    /// ownership of `kv_cache` so each layer's cached tensor data can be
+    /// moved directly into this step's input tensors rather than cloned —
+    /// the caller is about to replace it wholesale with a fresh cache
+    /// built from this step's outputs anyway.
+    ///
+    /// `new_token_count` is how many trailing entries of `token_ids`
+    /// haven't been fed into `kv_cache` yet (1 for ordinary autoregressive
+    /// steps, more than 1 when speculative decoding verifies a whole
+    /// drafted block in one forward pass). Ignored on the very first step
+    /// (`kv_cache` is `None`), which always feeds all of `token_ids`.
     fn prepare_inputs<'b>(
         &self,
         token_ids: &[i64],
-        kv_cache: &Option<KVCache>,
+        mut kv_cache: Option<KVCache>,
+        new_token_count: usize,
     ) -> Phi4Result<Vec<SessionInputValue<'b>>> {
         let batch_size = 1;
         let is_first_step = kv_cache.is_none();
-        
-        // For subsequent steps, we only need the last token
+
         let input_ids = if is_first_step {
             token_ids.to_vec()
         } else {
-            vec![*token_ids.last().unwrap()]
+            token_ids[token_ids.len() - new_token_count..].to_vec()
         };
-        
+
         let seq_len = input_ids.len();
         
         // Create input_ids tensor
@@ -141,73 +1164,181 @@ impl<'a> TextGenerator<'a> {
             SessionInputValue::from(attention_mask_tensor),
         ];
         
-        // Add KV cache inputs - for now, use empty cache
-        // TODO: Implement proper KV cache handling with ort 2.0 API
-        for _ in 0..self.config.num_layers {
-            // Empty key tensor: [batch_size, num_heads, 0, head_dim]
-            let empty_key = vec![0.0f32; 0];
-            let key_tensor = Value::from_array(([batch_size, self.config.num_heads, 0, self.config.head_dim], empty_key.into_boxed_slice()))
-                .map_err(|e| Phi4Error::InferenceFailed(format!("Failed to create KV key: {}", e)))?;
+        // Feed back each layer's cached key/value as past_key_values.{i}.
+        // From the second step on, reuses the real shape captured from the
+        // model's own `present.{i}` output rather than assuming
+        // [batch_size, num_heads, past_len, head_dim] from config, which
+        // can be wrong under grouped-query attention where the exported KV
+        // head count differs from num_heads. The very first step has no
+        // prior output to derive a shape from, so its empty placeholder
+        // necessarily relies on config.num_heads/head_dim matching the
+        // model's actual KV head count — set these to the model's KV head
+        // count (not its query head count) if they differ.
+        for i in 0..self.config.num_layers {
+            let empty_shape = vec![batch_size as i64, self.config.num_heads as i64, 0, self.config.head_dim as i64];
+
+            let (key_shape, key_data) = kv_cache
+                .as_mut()
+                .and_then(|cache| cache.cache.remove(&KVCache::key_name(i)))
+                .unwrap_or_else(|| (empty_shape.clone(), Vec::new()));
+            let key_tensor = Value::from_array((Self::shape_to_usize(&key_shape), key_data.into_boxed_slice()))
+                .map_err(|e| Phi4Error::InferenceFailed(format!("Failed to create KV key for layer {i}: {e}")))?;
             inputs.push(SessionInputValue::from(key_tensor));
-            
-            // Empty value tensor: [batch_size, num_heads, 0, head_dim]
-            let empty_value = vec![0.0f32; 0];
-            let value_tensor = Value::from_array(([batch_size, self.config.num_heads, 0, self.config.head_dim], empty_value.into_boxed_slice()))
-                .map_err(|e| Phi4Error::InferenceFailed(format!("Failed to create KV value: {}", e)))?;
+
+            let (value_shape, value_data) = kv_cache
+                .as_mut()
+                .and_then(|cache| cache.cache.remove(&KVCache::value_name(i)))
+                .unwrap_or_else(|| (empty_shape, Vec::new()));
+            let value_tensor = Value::from_array((Self::shape_to_usize(&value_shape), value_data.into_boxed_slice()))
+                .map_err(|e| Phi4Error::InferenceFailed(format!("Failed to create KV value for layer {i}: {e}")))?;
             inputs.push(SessionInputValue::from(value_tensor));
         }
-        
+
         Ok(inputs)
     }
 
-    /// Process model outputs to get next token
-    fn process_outputs(
-        &self,
-        outputs: &SessionOutputs,
-        current_ids: &[i64],
-    ) -> Phi4Result<i64> {
-        // Get logits output
+    /// Convert an ONNX tensor's `i64` shape dims (as captured from
+    /// `try_extract_raw_tensor`) into the `[usize; 4]` form `Value::from_array`
+    /// expects for a 4D key/value tensor
+    fn shape_to_usize(shape: &[i64]) -> [usize; 4] {
+        [shape[0] as usize, shape[1] as usize, shape[2] as usize, shape[3] as usize]
+    }
+
+    /// Extract the logits for just the last position from a `logits`
+    /// output shaped `[batch, seq_len, vocab_size]`. Kept separate from
+    /// `extract_all_position_logits` (rather than that plus a `.pop()`)
+    /// since every ordinary generation step only ever needs the last
+    /// position, and the first (prefill) step's `seq_len` can be the
+    /// whole prompt — copying every position just to discard all but one
+    /// would be wasteful there. Keep both in sync if the tensor layout
+    /// this reads ever changes.
+    fn extract_last_token_logits(&self, outputs: &SessionOutputs) -> Phi4Result<Vec<f32>> {
         let logits = outputs.get("logits")
             .ok_or_else(|| Phi4Error::InferenceFailed("No logits output found".to_string()))?;
-        
-        // Extract tensor data
+
         let (shape, data) = logits.try_extract_raw_tensor::<f32>()
             .map_err(|e| Phi4Error::InferenceFailed(format!("Failed to extract logits: {}", e)))?;
-        
-        // Get dimensions
+
         let last_position = shape[1] - 1;
         let vocab_size = shape[2];
-        
-        // Extract logits for last token
+
         let mut last_logits = vec![0.0f32; vocab_size];
         let offset = (last_position * vocab_size) as usize;
         for i in 0..vocab_size {
             last_logits[i] = data[offset + i];
         }
-        
-        // Apply repetition penalty
-        if self.config.repetition_penalty != 1.0 {
-            for &token_id in current_ids {
-                if (token_id as usize) < vocab_size {
-                    last_logits[token_id as usize] /= self.config.repetition_penalty;
-                }
+
+        Ok(last_logits)
+    }
+
+    /// Extract every position's logits from a `logits` output shaped
+    /// `[batch, seq_len, vocab_size]`, in position order. Used by
+    /// speculative decoding's verification pass, where each of the
+    /// several positions fed in one forward pass needs checking
+    /// individually rather than just the last one.
+    fn extract_all_position_logits(&self, outputs: &SessionOutputs) -> Phi4Result<Vec<Vec<f32>>> {
+        let logits = outputs.get("logits")
+            .ok_or_else(|| Phi4Error::InferenceFailed("No logits output found".to_string()))?;
+
+        let (shape, data) = logits.try_extract_raw_tensor::<f32>()
+            .map_err(|e| Phi4Error::InferenceFailed(format!("Failed to extract logits: {}", e)))?;
+
+        let seq_len = shape[1];
+        let vocab_size = shape[2];
+
+        let mut positions = Vec::with_capacity(seq_len as usize);
+        for pos in 0..seq_len {
+            let offset = (pos * vocab_size) as usize;
+            positions.push(data[offset..offset + vocab_size as usize].to_vec());
+        }
+
+        Ok(positions)
+    }
+
+    /// Penalize tokens already present in `prior_ids` in place, same as the
+    /// greedy/sampling path, so beam search doesn't rank degenerate
+    /// repeating continuations above ones `repetition_penalty` would have
+    /// suppressed there.
+    fn apply_repetition_penalty(&self, logits: &mut [f32], prior_ids: &[i64]) {
+        penalize_repeated_tokens(logits, prior_ids, self.config.repetition_penalty);
+    }
+
+    /// Subtract `frequency_penalty * count` and, once per token that's
+    /// appeared at all, `presence_penalty` from each already-seen token's
+    /// logit — the additive OpenAI-style counterpart to the multiplicative
+    /// `repetition_penalty` above, applied separately since either can be
+    /// used alone or together.
+    fn apply_frequency_presence_penalty(&self, logits: &mut [f32], prior_ids: &[i64]) {
+        penalize_frequency_presence(logits, prior_ids, self.config.frequency_penalty, self.config.presence_penalty);
+    }
+
+    /// Mask every logit not in `prefix_allowed_tokens_fn`'s result for
+    /// `prior_ids` to `-inf`, if a constraint function is set. A no-op
+    /// when there isn't one, or when it returns an empty result (which
+    /// means "unconstrained" for this step, not "nothing allowed").
+    fn apply_prefix_allowed_tokens_constraint(&self, logits: &mut [f32], prior_ids: &[i64]) {
+        let Some(f) = &self.prefix_allowed_tokens_fn else {
+            return;
+        };
+        let allowed = f(prior_ids);
+        if allowed.is_empty() {
+            return;
+        }
+        let allowed: HashSet<i64> = allowed.into_iter().collect();
+        for (token_id, logit) in logits.iter_mut().enumerate() {
+            if !allowed.contains(&(token_id as i64)) {
+                *logit = f32::NEG_INFINITY;
             }
         }
-        
-        // Sample next token
-        let next_token = if self.config.do_sample {
-            self.sample_token(&last_logits)?
+    }
+
+    /// Process model outputs to get the next token and, if
+    /// `config.output_scores` is set, its log-probability.
+    fn process_outputs(
+        &self,
+        outputs: &SessionOutputs,
+        current_ids: &[i64],
+        prompt_len: usize,
+    ) -> Phi4Result<(i64, Option<f32>)> {
+        let mut last_logits = self.extract_last_token_logits(outputs)?;
+        self.select_next_token(&mut last_logits, current_ids, prompt_len)
+    }
+
+    /// Apply every configured penalty/constraint to `logits` in place,
+    /// then pick the resulting next token exactly as `do_sample` says to:
+    /// greedy argmax when `false`, `sample_token` otherwise. Shared by
+    /// `process_outputs` and `generate_speculative`'s verification loop
+    /// so a future penalty/constraint added to one doesn't silently skip
+    /// the other and make speculative decoding diverge from the
+    /// non-speculative path it's meant to match (under `do_sample ==
+    /// false`).
+    ///
+    /// `prior_ids` is the full sequence so far (prompt included, same as
+    /// `apply_repetition_penalty`/`apply_frequency_presence_penalty`
+    /// want); `prompt_len` is used to trim it down to the generated-only
+    /// suffix for `apply_prefix_allowed_tokens_constraint` — see
+    /// `json_constraint`'s doc comment for why that distinction matters.
+    ///
+    /// When `config.output_scores` is set, also returns the chosen
+    /// token's log-probability under the post-penalty/constraint
+    /// distribution (i.e. before `sample_token`'s temperature/top-k/top-p
+    /// filtering narrows things further for sampling) — this is the
+    /// "real" per-token log-probability `GeneratedOutput::token_logprobs`
+    /// documents, not the filtered sampling distribution.
+    fn select_next_token(&self, logits: &mut [f32], prior_ids: &[i64], prompt_len: usize) -> Phi4Result<(i64, Option<f32>)> {
+        self.apply_repetition_penalty(logits, prior_ids);
+        self.apply_frequency_presence_penalty(logits, prior_ids);
+        self.apply_prefix_allowed_tokens_constraint(logits, &prior_ids[prompt_len..]);
+
+        let token_id = if self.config.do_sample {
+            self.sample_token(logits)?
         } else {
-            // Greedy decoding
-            last_logits
-                .iter()
-                .enumerate()
-                .max_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap())
-                .map(|(idx, _)| idx as i64)
-                .unwrap()
+            argmax(logits)
         };
-        
-        Ok(next_token)
+
+        let logprob = self.config.output_scores.then(|| log_softmax(logits)[token_id as usize]);
+
+        Ok((token_id, logprob))
     }
 
     /// Sample token from logits distribution
@@ -259,137 +1390,406 @@ impl<'a> TextGenerator<'a> {
 
     /// Apply top-k filtering to logits
     fn apply_top_k(&self, logits: &[f32]) -> Vec<f32> {
-        if self.config.top_k == 0 || self.config.top_k >= logits.len() {
-            return logits.to_vec();
-        }
-        
-        // Get indices of top-k values
-        let mut indexed: Vec<(usize, f32)> = logits
-            .iter()
-            .enumerate()
-            .map(|(i, &v)| (i, v))
-            .collect();
-        
-        indexed.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap());
-        
-        let top_k_indices: std::collections::HashSet<usize> = indexed
-            .iter()
-            .take(self.config.top_k)
-            .map(|(i, _)| *i)
-            .collect();
-        
-        // Set non-top-k values to -inf
-        logits
-            .iter()
-            .enumerate()
-            .map(|(i, &v)| {
-                if top_k_indices.contains(&i) {
-                    v
-                } else {
-                    f32::NEG_INFINITY
-                }
-            })
-            .collect()
+        filter_top_k(logits, self.config.top_k)
     }
 
     /// Apply top-p (nucleus) filtering to logits
     fn apply_top_p(&self, logits: &[f32]) -> Vec<f32> {
-        if self.config.top_p >= 1.0 {
-            return logits.to_vec();
-        }
-        
-        // Convert to probabilities for sorting
-        let max_logit = logits.iter().fold(f32::NEG_INFINITY, |a, &b| a.max(b));
-        let exp_logits: Vec<f32> = logits.iter().map(|&x| (x - max_logit).exp()).collect();
-        let sum_exp: f32 = exp_logits.iter().sum();
-        let probs: Vec<f32> = exp_logits.iter().map(|&x| x / sum_exp).collect();
-        
-        // Sort by probability
-        let mut indexed: Vec<(usize, f32)> = probs
-            .iter()
-            .enumerate()
-            .map(|(i, &p)| (i, p))
-            .collect();
-        
-        indexed.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap());
-        
-        // Find cutoff for top-p
-        let mut cumulative = 0.0;
-        let mut cutoff_idx = 0;
-        for (i, (_, prob)) in indexed.iter().enumerate() {
-            cumulative += prob;
-            if cumulative >= self.config.top_p {
-                cutoff_idx = i;
-                break;
+        filter_top_p(logits, self.config.top_p)
+    }
+
+    /// Extract each layer's `present.{i}.key`/`present.{i}.value` output
+    /// tensor from this step's outputs into a cache to feed back next step.
+    /// `past_len` is the cache's new sequence length (already includes the
+    /// positions just processed).
+    fn extract_kv_cache(&self, outputs: &SessionOutputs, past_len: usize) -> Phi4Result<KVCache> {
+        let mut cache = HashMap::with_capacity(self.config.num_layers * 2);
+
+        for i in 0..self.config.num_layers {
+            for name in [KVCache::key_name(i), KVCache::value_name(i)] {
+                let tensor = outputs.get(name.as_str())
+                    .ok_or_else(|| Phi4Error::InferenceFailed(format!("No {name} output found")))?;
+                let (shape, data) = tensor.try_extract_raw_tensor::<f32>()
+                    .map_err(|e| Phi4Error::InferenceFailed(format!("Failed to extract {name}: {e}")))?;
+                cache.insert(name, (shape.to_vec(), data.to_vec()));
             }
         }
-        
-        // Keep only tokens in top-p
-        let top_p_indices: std::collections::HashSet<usize> = indexed
-            .iter()
-            .take(cutoff_idx + 1)
-            .map(|(i, _)| *i)
-            .collect();
-        
-        logits
-            .iter()
-            .enumerate()
-            .map(|(i, &v)| {
-                if top_p_indices.contains(&i) {
-                    v
-                } else {
-                    f32::NEG_INFINITY
-                }
-            })
-            .collect()
-    }
 
-    /// Extract KV cache from model outputs
-    fn extract_kv_cache(&self, _outputs: &SessionOutputs) -> Phi4Result<KVCache> {
-        // TODO: Implement proper KV cache extraction with ort 2.0
-        // For now, return empty cache
-        Ok(KVCache { cache: HashMap::new() })
+        Ok(KVCache { cache, past_len })
     }
 
-    /// Check if generation should stop
+    /// Check if generation should stop on EOS or repetition. Does not
+    /// cover `stop_sequences` — see `matches_stop_sequence`, checked
+    /// separately by callers since (unlike an EOS token) the
+    /// stop-triggering token still needs to be appended before the
+    /// configured string is actually present in the output to trim.
+    ///
+    /// Structured-output callers that want reliable JSON termination
+    /// should use `json_constraint` via `with_prefix_allowed_tokens_fn`
+    /// (which forces `eos_token_id` once the top-level value closes)
+    /// instead of relying on a text heuristic here — this no longer
+    /// guesses at JSON completion itself.
     fn should_stop(&self, token_id: i64, generated_tokens: &[i64]) -> bool {
-        // Check for EOS token (typically 2 for many models)
-        if token_id == 2 || token_id == 0 {
-            return true;
+        is_eos_or_repetitive(token_id, &self.config.eos_token_ids, generated_tokens, self.config.repetition_window)
+    }
+
+    /// Decode the trailing `STOP_SEQUENCE_TAIL_TOKENS` of `generated_tokens`
+    /// plus `token_id` (about to be appended) and check whether any
+    /// configured `GenerationConfig::stop_sequences` appears in it.
+    /// Callers that get `true` back should still append `token_id` (unlike
+    /// `should_stop`'s EOS case) so the matched string is actually present
+    /// in the decoded output for `trim_stop_sequences` to remove.
+    fn matches_stop_sequence(&self, token_id: i64, generated_tokens: &[i64]) -> bool {
+        if self.config.stop_sequences.is_empty() {
+            return false;
         }
-        
-        // Check for repetition
-        if generated_tokens.len() >= 20 {
-            let recent = &generated_tokens[generated_tokens.len() - 20..];
-            let last_10 = &recent[10..];
-            let prev_10 = &recent[..10];
-            
-            if last_10 == prev_10 {
-                debug!("Repetition detected, stopping generation");
-                return true;
-            }
+
+        let tail_start = generated_tokens.len().saturating_sub(STOP_SEQUENCE_TAIL_TOKENS - 1);
+        let mut tail_ids: Vec<u32> = generated_tokens[tail_start..].iter().map(|&id| id as u32).collect();
+        tail_ids.push(token_id as u32);
+
+        let Ok(tail_text) = self.tokenizer.decode(&tail_ids, true) else {
+            return false;
+        };
+        self.config.stop_sequences.iter().any(|stop| tail_text.contains(stop.as_str()))
+    }
+
+    /// Remove whichever configured `stop_sequences` string appears
+    /// earliest in `text`, keeping only what was generated before it. A
+    /// no-op if none are configured or none matched. `generate_stream`
+    /// already emitted everything up to this point to `on_token` before
+    /// this runs, so there this only affects the final returned string,
+    /// not what was streamed.
+    fn trim_stop_sequences(&self, text: &mut String) {
+        trim_stop_sequences_from(text, &self.config.stop_sequences);
+    }
+}
+
+/// Penalize tokens already present in `prior_ids` by dividing their logit
+/// by `repetition_penalty` (a no-op at `1.0`) — see
+/// `TextGenerator::apply_repetition_penalty`.
+fn penalize_repeated_tokens(logits: &mut [f32], prior_ids: &[i64], repetition_penalty: f32) {
+    if repetition_penalty == 1.0 {
+        return;
+    }
+    let vocab_size = logits.len();
+    for &token_id in prior_ids {
+        if (token_id as usize) < vocab_size {
+            logits[token_id as usize] /= repetition_penalty;
         }
-        
-        // Check for JSON completion (useful for structured outputs)
-        if generated_tokens.len() > 5 {
-            // Simple check - in practice we'd decode and verify
-            let last_few: Vec<u32> = generated_tokens
-                .iter()
-                .rev()
-                .take(5)
-                .map(|&id| id as u32)
-                .collect();
-            
-            if let Ok(text) = self.tokenizer.decode(&last_few, false) {
-                if text.contains("}") && text.contains("\n") {
-                    debug!("JSON completion detected");
-                    return true;
-                }
-            }
+    }
+}
+
+/// Subtract `frequency_penalty * count` and, once per token that's
+/// appeared at all, `presence_penalty` from each already-seen token's
+/// logit — see `TextGenerator::apply_frequency_presence_penalty`.
+fn penalize_frequency_presence(logits: &mut [f32], prior_ids: &[i64], frequency_penalty: f32, presence_penalty: f32) {
+    if frequency_penalty == 0.0 && presence_penalty == 0.0 {
+        return;
+    }
+    let vocab_size = logits.len();
+    let mut counts: HashMap<i64, u32> = HashMap::new();
+    for &token_id in prior_ids {
+        *counts.entry(token_id).or_insert(0) += 1;
+    }
+    for (token_id, count) in counts {
+        if (token_id as usize) < vocab_size {
+            logits[token_id as usize] -= frequency_penalty * count as f32 + presence_penalty;
         }
-        
-        false
     }
 }
 
-// Add rand dependency to Cargo.toml for sampling
\ No newline at end of file
+/// Mask every logit outside the `top_k` largest to `-inf` — see
+/// `TextGenerator::apply_top_k`.
+fn filter_top_k(logits: &[f32], top_k: usize) -> Vec<f32> {
+    if top_k == 0 || top_k >= logits.len() {
+        return logits.to_vec();
+    }
+
+    let mut indexed: Vec<(usize, f32)> = logits
+        .iter()
+        .enumerate()
+        .map(|(i, &v)| (i, v))
+        .collect();
+
+    indexed.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap());
+
+    let top_k_indices: HashSet<usize> = indexed
+        .iter()
+        .take(top_k)
+        .map(|(i, _)| *i)
+        .collect();
+
+    logits
+        .iter()
+        .enumerate()
+        .map(|(i, &v)| if top_k_indices.contains(&i) { v } else { f32::NEG_INFINITY })
+        .collect()
+}
+
+/// Mask every logit outside the smallest set whose cumulative probability
+/// reaches `top_p` to `-inf` — see `TextGenerator::apply_top_p`.
+fn filter_top_p(logits: &[f32], top_p: f32) -> Vec<f32> {
+    if top_p >= 1.0 {
+        return logits.to_vec();
+    }
+
+    let max_logit = logits.iter().fold(f32::NEG_INFINITY, |a, &b| a.max(b));
+    let exp_logits: Vec<f32> = logits.iter().map(|&x| (x - max_logit).exp()).collect();
+    let sum_exp: f32 = exp_logits.iter().sum();
+    let probs: Vec<f32> = exp_logits.iter().map(|&x| x / sum_exp).collect();
+
+    let mut indexed: Vec<(usize, f32)> = probs.iter().enumerate().map(|(i, &p)| (i, p)).collect();
+    indexed.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap());
+
+    let mut cumulative = 0.0;
+    let mut cutoff_idx = 0;
+    for (i, (_, prob)) in indexed.iter().enumerate() {
+        cumulative += prob;
+        if cumulative >= top_p {
+            cutoff_idx = i;
+            break;
+        }
+    }
+
+    let top_p_indices: HashSet<usize> = indexed.iter().take(cutoff_idx + 1).map(|(i, _)| *i).collect();
+
+    logits
+        .iter()
+        .enumerate()
+        .map(|(i, &v)| if top_p_indices.contains(&i) { v } else { f32::NEG_INFINITY })
+        .collect()
+}
+
+/// Whether `token_id` should end generation: it's one of `eos_token_ids`,
+/// or the last half of a `repetition_window`-sized trailing slice of
+/// `generated_tokens` repeats the half before it — see
+/// `TextGenerator::should_stop`.
+fn is_eos_or_repetitive(token_id: i64, eos_token_ids: &[i64], generated_tokens: &[i64], repetition_window: usize) -> bool {
+    if eos_token_ids.contains(&token_id) {
+        return true;
+    }
+
+    // `half` rounds down so both halves compare equal lengths even for an
+    // odd `repetition_window` — comparing against the odd window itself
+    // would split it unevenly and the slice `==` below would never match
+    // regardless of content.
+    let half = repetition_window / 2;
+    if half >= 1 && generated_tokens.len() >= half * 2 {
+        let recent = &generated_tokens[generated_tokens.len() - half * 2..];
+        let last_half = &recent[half..];
+        let prev_half = &recent[..half];
+
+        if last_half == prev_half {
+            debug!("Repetition detected, stopping generation");
+            return true;
+        }
+    }
+
+    false
+}
+
+/// Remove whichever configured `stop_sequences` string appears earliest in
+/// `text`, keeping only what was generated before it — see
+/// `TextGenerator::trim_stop_sequences`.
+fn trim_stop_sequences_from(text: &mut String, stop_sequences: &[String]) {
+    let mut cut = text.len();
+    for stop in stop_sequences {
+        if let Some(pos) = text.find(stop.as_str()) {
+            cut = cut.min(pos);
+        }
+    }
+    text.truncate(cut);
+}
+
+/// Length-penalty-adjusted score used to rank finished beams: `score /
+/// len^length_penalty`, where `len` is floored at `1.0` so a beam that
+/// finishes on its very first generated token (0 generated tokens) doesn't
+/// divide by zero (`0f32.powf(length_penalty)` is `0.0`) — see
+/// `TextGenerator::generate_beam_search`.
+fn length_penalized_score(score: f32, generated_len: usize, length_penalty: f32) -> f32 {
+    let len = (generated_len as f32).max(1.0);
+    score / len.powf(length_penalty)
+}
+
+/// The KV-cache length to roll the main and draft caches back to after a
+/// speculative-decoding round: everything accepted except the newest
+/// token, restoring the "cache lags one token behind current_ids"
+/// invariant for the next round — see `TextGenerator::generate_speculative`.
+fn speculative_cache_rollback_len(len_before_round: usize, accepted_count: usize) -> usize {
+    len_before_round + accepted_count - 1
+}
+
+/// The final accepted-token list for one speculative-decoding round: the
+/// draft's first `accepted_count` tokens (the run the main model agreed
+/// with) plus, if the draft diverged before running out, `override_token`
+/// — the main model's own choice at the first disagreement — see
+/// `TextGenerator::generate_speculative`.
+fn accepted_tokens(draft_tokens: &[i64], accepted_count: usize, override_token: Option<i64>) -> Vec<i64> {
+    let mut tokens = draft_tokens[..accepted_count].to_vec();
+    if let Some(token) = override_token {
+        tokens.push(token);
+    }
+    tokens
+}
+
+// Add rand dependency to Cargo.toml for sampling
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BATCH: usize = 1;
+    const NUM_HEADS: usize = 2;
+    const HEAD_DIM: usize = 4;
+
+    fn kv_cache_with_seq_len(seq_len: usize) -> KVCache {
+        let shape = vec![BATCH as i64, NUM_HEADS as i64, seq_len as i64, HEAD_DIM as i64];
+        let data = vec![0.0f32; BATCH * NUM_HEADS * seq_len * HEAD_DIM];
+        let mut cache = HashMap::new();
+        cache.insert(KVCache::key_name(0), (shape.clone(), data.clone()));
+        cache.insert(KVCache::value_name(0), (shape, data));
+        KVCache { cache, past_len: seq_len }
+    }
+
+    #[test]
+    fn test_kv_cache_truncate_shrinks_seq_len_and_data() {
+        let mut cache = kv_cache_with_seq_len(8);
+        cache.truncate(3);
+        assert_eq!(cache.past_len, 3);
+        let (shape, data) = &cache.cache[&KVCache::key_name(0)];
+        assert_eq!(shape[2], 3);
+        assert_eq!(data.len(), BATCH * NUM_HEADS * 3 * HEAD_DIM);
+    }
+
+    #[test]
+    fn test_kv_cache_truncate_is_noop_when_new_len_not_shorter() {
+        let mut cache = kv_cache_with_seq_len(5);
+        cache.truncate(5);
+        assert_eq!(cache.past_len, 5);
+        cache.truncate(8);
+        assert_eq!(cache.past_len, 5);
+        let (shape, data) = &cache.cache[&KVCache::key_name(0)];
+        assert_eq!(shape[2], 5);
+        assert_eq!(data.len(), BATCH * NUM_HEADS * 5 * HEAD_DIM);
+    }
+
+    #[test]
+    fn test_length_penalized_score_floors_zero_length_at_one() {
+        // 0 generated tokens must not divide by zero (0f32.powf(x) == 0.0)
+        let score = length_penalized_score(2.0, 0, 1.0);
+        assert_eq!(score, 2.0);
+    }
+
+    #[test]
+    fn test_length_penalized_score_prefers_longer_beam_under_penalty_below_one() {
+        // cumulative log-prob scores are <= 0 and grow more negative with
+        // length, so normalizing lets a longer beam outscore a shorter one
+        let short = length_penalized_score(-4.0, 2, 0.5);
+        let long = length_penalized_score(-4.0, 8, 0.5);
+        assert!(long > short);
+    }
+
+    #[test]
+    fn test_penalize_repeated_tokens_divides_seen_logits() {
+        let mut logits = vec![1.0, 2.0, -1.0];
+        // token 1 appears twice in prior_ids, so it's divided by the penalty twice
+        penalize_repeated_tokens(&mut logits, &[1, 1, 2], 2.0);
+        assert_eq!(logits, vec![1.0, 0.5, -0.5]);
+    }
+
+    #[test]
+    fn test_penalize_repeated_tokens_noop_at_one() {
+        let mut logits = vec![1.0, 2.0, -1.0];
+        penalize_repeated_tokens(&mut logits, &[0, 1, 2], 1.0);
+        assert_eq!(logits, vec![1.0, 2.0, -1.0]);
+    }
+
+    #[test]
+    fn test_penalize_frequency_presence_scales_with_count_and_adds_presence_once() {
+        let mut logits = vec![5.0, 5.0, 5.0];
+        penalize_frequency_presence(&mut logits, &[0, 0, 0, 1], 1.0, 0.5);
+        assert_eq!(logits[0], 5.0 - 3.0 - 0.5);
+        assert_eq!(logits[1], 5.0 - 1.0 - 0.5);
+        assert_eq!(logits[2], 5.0);
+    }
+
+    #[test]
+    fn test_filter_top_k_keeps_only_largest_k() {
+        let logits = vec![0.1, 0.9, 0.5, 0.3];
+        let filtered = filter_top_k(&logits, 2);
+        let kept: Vec<bool> = filtered.iter().map(|v| v.is_finite()).collect();
+        assert_eq!(kept, vec![false, true, true, false]);
+    }
+
+    #[test]
+    fn test_filter_top_k_zero_or_oversized_is_noop() {
+        let logits = vec![0.1, 0.9, 0.5];
+        assert_eq!(filter_top_k(&logits, 0), logits);
+        assert_eq!(filter_top_k(&logits, 10), logits);
+    }
+
+    #[test]
+    fn test_filter_top_p_keeps_smallest_covering_set() {
+        // softmax([10, 0, 0, 0]) puts almost all mass on index 0
+        let logits = vec![10.0, 0.0, 0.0, 0.0];
+        let filtered = filter_top_p(&logits, 0.9);
+        assert!(filtered[0].is_finite());
+        assert!(filtered[1..].iter().all(|v| !v.is_finite()));
+    }
+
+    #[test]
+    fn test_filter_top_p_at_or_above_one_is_noop() {
+        let logits = vec![0.1, 0.9, 0.5];
+        assert_eq!(filter_top_p(&logits, 1.0), logits);
+    }
+
+    #[test]
+    fn test_is_eos_or_repetitive_detects_eos_token() {
+        assert!(is_eos_or_repetitive(7, &[7], &[1, 2, 3], 4));
+    }
+
+    #[test]
+    fn test_is_eos_or_repetitive_detects_repeated_window() {
+        // last two tokens repeat the two before them
+        let generated = vec![1, 2, 1, 2];
+        assert!(is_eos_or_repetitive(99, &[], &generated, 4));
+    }
+
+    #[test]
+    fn test_is_eos_or_repetitive_false_when_neither() {
+        let generated = vec![1, 2, 3, 4];
+        assert!(!is_eos_or_repetitive(99, &[7], &generated, 4));
+    }
+
+    #[test]
+    fn test_trim_stop_sequences_from_cuts_at_earliest_match() {
+        let mut text = "hello world STOP trailing".to_string();
+        trim_stop_sequences_from(&mut text, &["STOP".to_string(), "world".to_string()]);
+        assert_eq!(text, "hello ");
+    }
+
+    #[test]
+    fn test_trim_stop_sequences_from_noop_when_no_match() {
+        let mut text = "hello world".to_string();
+        trim_stop_sequences_from(&mut text, &["STOP".to_string()]);
+        assert_eq!(text, "hello world");
+    }
+
+    #[test]
+    fn test_speculative_cache_rollback_len() {
+        assert_eq!(speculative_cache_rollback_len(10, 3), 12);
+    }
+
+    #[test]
+    fn test_accepted_tokens_appends_override_on_divergence() {
+        let draft = vec![1, 2, 3, 4];
+        assert_eq!(accepted_tokens(&draft, 2, Some(99)), vec![1, 2, 99]);
+    }
+
+    #[test]
+    fn test_accepted_tokens_no_override_when_fully_accepted() {
+        let draft = vec![1, 2, 3];
+        assert_eq!(accepted_tokens(&draft, 3, None), vec![1, 2, 3]);
+    }
+}
\ No newline at end of file
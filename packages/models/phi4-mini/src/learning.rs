@@ -0,0 +1,209 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Phi4Error, Phi4Result};
+
+// Add bincode dependency to Cargo.toml
+
+/// Bump whenever `LearningResults`'s shape changes incompatibly, so state
+/// written by an older build is discarded on load instead of
+/// misinterpreted by `bincode`
+const LEARNING_RESULTS_VERSION: u32 = 1;
+
+/// Reusable state an `AnalyticUnit` learns from a corpus via
+/// `AnalyticUnit::learn` and can reload via `AnalyticUnit::load_state` on a
+/// later startup, keyed by unit id (`AnalyticUnit::id`) so one blob can
+/// carry state for several units at once.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LearningResults {
+    version: u32,
+
+    /// Calibrated confidence prior to use instead of a hard-coded guess
+    pub calibrated_confidence_priors: HashMap<String, f32>,
+
+    /// Distinct pattern names observed in the training corpus, sorted; every
+    /// observed pattern is kept, however rarely it appeared
+    pub pattern_fingerprints: HashMap<String, Vec<String>>,
+
+    /// Cached per-metric threshold baselines (e.g. a historical mean) a
+    /// unit can use instead of a fixed, hand-picked bound
+    pub threshold_baselines: HashMap<String, f32>,
+}
+
+impl LearningResults {
+    pub fn new() -> Self {
+        Self {
+            version: LEARNING_RESULTS_VERSION,
+            ..Default::default()
+        }
+    }
+
+    /// Load and validate learned state from `path`. Returns `None` (rather
+    /// than an error) if the file is missing, corrupt, or was written by an
+    /// incompatible version — any of these just means "start cold", not a
+    /// hard failure.
+    pub fn load_from(path: &Path) -> Option<Self> {
+        let bytes = std::fs::read(path).ok()?;
+        let results: Self = bincode::deserialize(&bytes).ok()?;
+
+        if results.version != LEARNING_RESULTS_VERSION {
+            log::warn!(
+                "Discarding learned state at {}: version {} is incompatible with the current version {}",
+                path.display(),
+                results.version,
+                LEARNING_RESULTS_VERSION
+            );
+            return None;
+        }
+        Some(results)
+    }
+
+    /// Combine several `LearningResults` (e.g. one per corpus item, for a
+    /// caller training one item at a time to avoid holding a shared engine
+    /// lock for an entire corpus) into one: confidence priors are averaged
+    /// and pattern fingerprints unioned, per unit id.
+    pub fn combine(results: Vec<LearningResults>) -> LearningResults {
+        let mut confidence_sums: HashMap<String, (f32, u32)> = HashMap::new();
+        let mut fingerprints: HashMap<String, std::collections::HashSet<String>> = HashMap::new();
+        let mut threshold_baselines: HashMap<String, f32> = HashMap::new();
+
+        for result in results {
+            for (unit_id, confidence) in result.calibrated_confidence_priors {
+                let entry = confidence_sums.entry(unit_id).or_insert((0.0, 0));
+                entry.0 += confidence;
+                entry.1 += 1;
+            }
+            for (unit_id, patterns) in result.pattern_fingerprints {
+                fingerprints.entry(unit_id).or_default().extend(patterns);
+            }
+            // Last one wins per metric, unlike the averaging/union applied to
+            // calibrated_confidence_priors/pattern_fingerprints above
+            threshold_baselines.extend(result.threshold_baselines);
+        }
+
+        let mut combined = LearningResults::new();
+        combined.calibrated_confidence_priors = confidence_sums
+            .into_iter()
+            .map(|(unit_id, (sum, count))| (unit_id, sum / count as f32))
+            .collect();
+        combined.pattern_fingerprints = fingerprints
+            .into_iter()
+            .map(|(unit_id, patterns)| {
+                let mut patterns: Vec<String> = patterns.into_iter().collect();
+                patterns.sort();
+                (unit_id, patterns)
+            })
+            .collect();
+        combined.threshold_baselines = threshold_baselines;
+        combined
+    }
+
+    pub fn save_to(&self, path: &Path) -> Phi4Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| Phi4Error::InferenceFailed(format!("Failed to create learned-state directory: {e}")))?;
+        }
+        let bytes = bincode::serialize(self)
+            .map_err(|e| Phi4Error::InferenceFailed(format!("Failed to serialize learned state: {e}")))?;
+        std::fs::write(path, bytes)
+            .map_err(|e| Phi4Error::InferenceFailed(format!("Failed to write learned state to {}: {e}", path.display())))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn results_with(confidence: f32, patterns: &[&str], threshold: Option<(&str, f32)>) -> LearningResults {
+        let mut results = LearningResults::new();
+        results.calibrated_confidence_priors.insert("unit".to_string(), confidence);
+        results.pattern_fingerprints.insert(
+            "unit".to_string(),
+            patterns.iter().map(|p| p.to_string()).collect(),
+        );
+        if let Some((metric, value)) = threshold {
+            results.threshold_baselines.insert(metric.to_string(), value);
+        }
+        results
+    }
+
+    #[test]
+    fn test_combine_averages_confidence_priors_per_unit() {
+        let combined = LearningResults::combine(vec![
+            results_with(0.2, &[], None),
+            results_with(0.8, &[], None),
+        ]);
+        assert_eq!(combined.calibrated_confidence_priors["unit"], 0.5);
+    }
+
+    #[test]
+    fn test_combine_unions_and_sorts_pattern_fingerprints() {
+        let combined = LearningResults::combine(vec![
+            results_with(0.5, &["b", "a"], None),
+            results_with(0.5, &["a", "c"], None),
+        ]);
+        assert_eq!(combined.pattern_fingerprints["unit"], vec!["a", "b", "c"]);
+    }
+
+    #[test]
+    fn test_combine_threshold_baselines_last_wins() {
+        let combined = LearningResults::combine(vec![
+            results_with(0.5, &[], Some(("complexity", 1.0))),
+            results_with(0.5, &[], Some(("complexity", 2.0))),
+        ]);
+        assert_eq!(combined.threshold_baselines["complexity"], 2.0);
+    }
+
+    #[test]
+    fn test_combine_empty_input_yields_empty_results() {
+        let combined = LearningResults::combine(Vec::new());
+        assert!(combined.calibrated_confidence_priors.is_empty());
+        assert!(combined.pattern_fingerprints.is_empty());
+        assert!(combined.threshold_baselines.is_empty());
+    }
+
+    #[test]
+    fn test_load_from_missing_file_returns_none() {
+        let path = std::env::temp_dir().join(format!("phi4_learning_test_missing_{}", std::process::id()));
+        assert!(LearningResults::load_from(&path).is_none());
+    }
+
+    #[test]
+    fn test_load_from_corrupt_bytes_returns_none() {
+        let path = std::env::temp_dir().join(format!("phi4_learning_test_corrupt_{}", std::process::id()));
+        std::fs::write(&path, b"not a valid bincode payload").unwrap();
+
+        assert!(LearningResults::load_from(&path).is_none());
+
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn test_load_from_version_mismatch_returns_none() {
+        let path = std::env::temp_dir().join(format!("phi4_learning_test_version_{}", std::process::id()));
+        let mut stale = LearningResults::new();
+        stale.version = LEARNING_RESULTS_VERSION + 1;
+        std::fs::write(&path, bincode::serialize(&stale).unwrap()).unwrap();
+
+        assert!(LearningResults::load_from(&path).is_none());
+
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn test_save_to_then_load_from_round_trips() {
+        let path = std::env::temp_dir().join(format!("phi4_learning_test_roundtrip_{}", std::process::id()));
+        let results = results_with(0.75, &["a", "b"], Some(("complexity", 3.0)));
+        results.save_to(&path).unwrap();
+
+        let loaded = LearningResults::load_from(&path).unwrap();
+        assert_eq!(loaded.calibrated_confidence_priors, results.calibrated_confidence_priors);
+        assert_eq!(loaded.pattern_fingerprints, results.pattern_fingerprints);
+        assert_eq!(loaded.threshold_baselines, results.threshold_baselines);
+
+        std::fs::remove_file(&path).ok();
+    }
+}
@@ -26,9 +26,19 @@ mod cognitive;
 mod quantization;
 mod download;
 mod generation;
+mod tokenizer;
+mod analytic_unit;
+mod threshold_unit;
+mod detection_runner;
+mod learning;
 
 pub use engine::Phi4MiniEngine;
 pub use cognitive::{Phi4Analysis, CognitiveAnalysis, ReasoningStep};
+pub use tokenizer::Phi4Tokenizer;
+pub use analytic_unit::{AnalyticInput, AnalyticService, AnalyticUnit};
+pub use threshold_unit::{ThresholdAnalyticUnit, ThresholdCondition, ThresholdConfig};
+pub use detection_runner::{DetectionRunner, DetectionRunnerConfig};
+pub use learning::LearningResults;
 
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
@@ -52,26 +62,44 @@ pub enum Phi4Error {
     InferenceFailed(String),
 }
 
+/// An ONNX Runtime execution provider `Phi4MiniEngine` can be asked to
+/// register. Kept as a small repo-local enum (rather than exposing `ort`'s
+/// own execution-provider types on `Phi4Config`) so the config stays plain
+/// data and serializable.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ExecutionProvider {
+    Cuda,
+    DirectMl,
+    CoreMl,
+    Cpu,
+}
+
 /// Configuration for Phi-4 Mini engine
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Phi4Config {
     /// Path to the ONNX model file
     pub model_path: String,
-    
+
     /// Path to the tokenizer file
     pub tokenizer_path: String,
-    
+
     /// Maximum sequence length
     pub max_length: usize,
-    
+
     /// Temperature for sampling (0.0 = deterministic)
     pub temperature: f32,
-    
+
     /// Number of threads for inference
     pub num_threads: usize,
-    
+
     /// Enable GPU acceleration if available
     pub use_gpu: bool,
+
+    /// Execution providers to try, in order, before falling back to CPU
+    /// when `use_gpu` is set. Empty (the default) means "pick a sensible
+    /// platform default order" — CUDA, then DirectML on Windows / CoreML
+    /// on macOS.
+    pub preferred_providers: Vec<ExecutionProvider>,
 }
 
 impl Default for Phi4Config {
@@ -83,6 +111,7 @@ impl Default for Phi4Config {
             temperature: 0.7,
             num_threads: 4,
             use_gpu: true,
+            preferred_providers: Vec::new(),
         }
     }
 }
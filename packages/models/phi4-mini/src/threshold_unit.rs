@@ -0,0 +1,194 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::analytic_unit::{AnalyticInput, AnalyticUnit};
+use crate::cognitive::ArchitecturalAnalysis;
+use crate::{Phi4Analysis, Phi4Error, Phi4Result};
+
+/// Which side of `bound` counts as a breach
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ThresholdCondition {
+    Above,
+    Below,
+}
+
+/// Configuration for a `ThresholdAnalyticUnit`: where to find the metric
+/// series in `project_data` (dot-separated path to a JSON array of
+/// numbers), the bound, and which side of it is a breach.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ThresholdConfig {
+    pub metric_path: String,
+    pub bound: f32,
+    pub condition: ThresholdCondition,
+}
+
+/// Deterministic, sub-millisecond complement to the Phi-4 unit: scans a
+/// numeric metric series (complexity, churn, coverage, ...) for contiguous
+/// runs that cross a configured bound, without spinning up the ONNX model.
+pub struct ThresholdAnalyticUnit {
+    config: ThresholdConfig,
+}
+
+impl ThresholdAnalyticUnit {
+    pub fn new(config: ThresholdConfig) -> Self {
+        Self { config }
+    }
+
+    /// Walk `project_data` along `metric_path`'s dot-separated segments and
+    /// collect the array found there as an `f32` series. Returns `None` if
+    /// the path doesn't resolve to a JSON array of numbers.
+    fn series_at(project_data: &Value, metric_path: &str) -> Option<Vec<f32>> {
+        let mut current = project_data;
+        for segment in metric_path.split('.') {
+            current = current.get(segment)?;
+        }
+        current
+            .as_array()?
+            .iter()
+            .map(|v| v.as_f64().map(|f| f as f32))
+            .collect()
+    }
+
+    fn breaches(&self, value: f32) -> bool {
+        match self.config.condition {
+            ThresholdCondition::Above => value > self.config.bound,
+            ThresholdCondition::Below => value < self.config.bound,
+        }
+    }
+
+    /// Normalized exceedance of `value` past `bound`, clamped to `1.0`
+    fn exceedance(&self, value: f32) -> f32 {
+        let bound = self.config.bound;
+        if bound == 0.0 {
+            return if self.breaches(value) { 1.0 } else { 0.0 };
+        }
+        ((value - bound).abs() / bound.abs()).min(1.0)
+    }
+
+    fn metric_name(&self) -> &str {
+        self.config
+            .metric_path
+            .rsplit('.')
+            .next()
+            .unwrap_or(&self.config.metric_path)
+    }
+
+    /// Record one contiguous breaching segment `series[start..end]` into
+    /// `analysis`/`architectural`, returning its average exceedance so the
+    /// caller can fold it into the overall confidence.
+    fn emit_segment(
+        &self,
+        analysis: &mut Phi4Analysis,
+        architectural: &mut ArchitecturalAnalysis,
+        series: &[f32],
+        start: usize,
+        end: usize,
+    ) -> f32 {
+        let segment = &series[start..end];
+        let confidence =
+            segment.iter().map(|&v| self.exceedance(v)).sum::<f32>() / segment.len() as f32;
+
+        analysis.add_pattern(format!("threshold_breach:{}", self.metric_name()), confidence);
+        architectural.add_suggestion(format!(
+            "`{}` {} {} across indices {}..{} (values: {:?})",
+            self.config.metric_path,
+            match self.config.condition {
+                ThresholdCondition::Above => "exceeds",
+                ThresholdCondition::Below => "falls below",
+            },
+            self.config.bound,
+            start,
+            end,
+            segment
+        ));
+
+        confidence
+    }
+}
+
+#[async_trait::async_trait]
+impl AnalyticUnit for ThresholdAnalyticUnit {
+    fn id(&self) -> &str {
+        "threshold"
+    }
+
+    fn supports(&self, input: &AnalyticInput) -> bool {
+        Self::series_at(&input.project_data, &self.config.metric_path).is_some()
+    }
+
+    async fn analyze(&self, input: &AnalyticInput) -> Phi4Result<Phi4Analysis> {
+        let series = Self::series_at(&input.project_data, &self.config.metric_path).ok_or_else(|| {
+            Phi4Error::InvalidInput(format!(
+                "no numeric series found at `{}`",
+                self.config.metric_path
+            ))
+        })?;
+
+        let mut analysis = Phi4Analysis::new(0.0, format!("Threshold scan of `{}`", self.config.metric_path));
+        let mut architectural = ArchitecturalAnalysis::new();
+        let mut segment_confidences = Vec::new();
+
+        let mut segment_start: Option<usize> = None;
+        for (i, &value) in series.iter().enumerate() {
+            if self.breaches(value) {
+                segment_start.get_or_insert(i);
+            } else if let Some(start) = segment_start.take() {
+                segment_confidences.push(self.emit_segment(&mut analysis, &mut architectural, &series, start, i));
+            }
+        }
+        if let Some(start) = segment_start {
+            segment_confidences.push(self.emit_segment(&mut analysis, &mut architectural, &series, start, series.len()));
+        }
+
+        analysis.confidence = if segment_confidences.is_empty() {
+            0.0
+        } else {
+            segment_confidences.iter().sum::<f32>() / segment_confidences.len() as f32
+        };
+        analysis.set_architectural_analysis(architectural);
+
+        Ok(analysis)
+    }
+}
+
+// Add tokio "macros" and "rt" dev-dependency features to Cargo.toml for #[tokio::test]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn input_with_series(series: Vec<f64>) -> AnalyticInput {
+        AnalyticInput {
+            prompt: String::new(),
+            project_data: json!({ "metrics": { "complexity": series } }),
+        }
+    }
+
+    #[tokio::test]
+    async fn detects_a_single_above_threshold_segment() {
+        let unit = ThresholdAnalyticUnit::new(ThresholdConfig {
+            metric_path: "metrics.complexity".to_string(),
+            bound: 10.0,
+            condition: ThresholdCondition::Above,
+        });
+        let input = input_with_series(vec![1.0, 2.0, 15.0, 20.0, 3.0]);
+
+        assert!(unit.supports(&input));
+        let analysis = unit.analyze(&input).await.unwrap();
+
+        assert_eq!(analysis.pattern_detection.detected_patterns, vec!["threshold_breach:complexity"]);
+        assert!(analysis.confidence > 0.0);
+    }
+
+    #[tokio::test]
+    async fn does_not_support_missing_metric_path() {
+        let unit = ThresholdAnalyticUnit::new(ThresholdConfig {
+            metric_path: "metrics.coverage".to_string(),
+            bound: 0.8,
+            condition: ThresholdCondition::Below,
+        });
+        let input = input_with_series(vec![1.0, 2.0]);
+
+        assert!(!unit.supports(&input));
+    }
+}
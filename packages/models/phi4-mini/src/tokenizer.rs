@@ -0,0 +1,46 @@
+use tokenizers::Tokenizer;
+
+use crate::{Phi4Error, Phi4Result};
+
+/// Native Rust tokenizer for Phi-4, loaded directly from the HF fast-tokenizer
+/// JSON via the `tokenizers` crate so inference never needs Python at runtime
+pub struct Phi4Tokenizer {
+    inner: Tokenizer,
+}
+
+impl Phi4Tokenizer {
+    /// Load a tokenizer from a HF fast-tokenizer JSON file
+    pub fn from_file(tokenizer_path: &str) -> Phi4Result<Self> {
+        let inner = Tokenizer::from_file(tokenizer_path).map_err(Phi4Error::TokenizerError)?;
+        Ok(Self { inner })
+    }
+
+    /// Encode text into token ids
+    pub fn encode(&self, text: &str, add_special_tokens: bool) -> Phi4Result<Vec<i64>> {
+        let encoding = self
+            .inner
+            .encode(text, add_special_tokens)
+            .map_err(Phi4Error::TokenizerError)?;
+
+        Ok(encoding.get_ids().iter().map(|&id| id as i64).collect())
+    }
+
+    /// Decode token ids back into text
+    pub fn decode(&self, ids: &[i64], skip_special_tokens: bool) -> Phi4Result<String> {
+        let ids: Vec<u32> = ids.iter().map(|&id| id as u32).collect();
+        self.inner
+            .decode(&ids, skip_special_tokens)
+            .map_err(Phi4Error::TokenizerError)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_file_rejects_missing_path() {
+        let result = Phi4Tokenizer::from_file("/nonexistent/phi4-tokenizer.json");
+        assert!(result.is_err());
+    }
+}
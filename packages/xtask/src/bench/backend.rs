@@ -0,0 +1,118 @@
+use std::process::{Child, Command};
+use std::time::{Duration, Instant};
+
+use futures_util::{SinkExt, StreamExt};
+use serde_json::Value;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{connect_async, MaybeTlsStream, WebSocketStream};
+
+use super::workload::Workload;
+
+/// Same endpoint `BackendManager` connects the desktop app to (see
+/// `packages/desktop/src-tauri/src/backend_manager.rs`)
+const BACKEND_ADDR: &str = "127.0.0.1:3456";
+const CONNECT_RETRY_DELAY: Duration = Duration::from_millis(250);
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+
+type WsStream = WebSocketStream<MaybeTlsStream<tokio::net::TcpStream>>;
+
+/// A single request/response connection to the backend, dedicated to this
+/// benchmark run — no reconnect logic, since a dropped connection means the
+/// run itself failed and should be reported as such rather than retried.
+pub struct BenchClient {
+    backend_process: Child,
+    ws: WsStream,
+    next_id: u64,
+}
+
+impl BenchClient {
+    /// Send `command`/`args` as a `send_guru_command`-shaped frame and wait
+    /// for the matching response, timing only the round trip.
+    async fn call(&mut self, command: &str, args: &[Value]) -> anyhow::Result<Duration> {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        let frame = serde_json::json!({
+            "id": id,
+            "type": "command",
+            "command": command,
+            "args": args,
+        });
+
+        let start = Instant::now();
+        self.ws.send(Message::Text(frame.to_string())).await?;
+
+        loop {
+            let message = self
+                .ws
+                .next()
+                .await
+                .ok_or_else(|| anyhow::anyhow!("backend connection closed mid-workload"))??;
+
+            let Message::Text(text) = message else {
+                continue;
+            };
+            let Ok(response) = serde_json::from_str::<Value>(&text) else {
+                continue;
+            };
+            if response.get("id").and_then(|v| v.as_u64()) != Some(id) {
+                continue; // a push frame or a stale response; keep waiting
+            }
+
+            if let Some(error) = response.get("error") {
+                anyhow::bail!("backend returned an error for `{command}`: {error}");
+            }
+            return Ok(start.elapsed());
+        }
+    }
+
+    pub async fn shutdown(mut self) {
+        let _ = self.ws.close(None).await;
+        let _ = self.backend_process.kill();
+    }
+}
+
+/// Run `workload.warmup_iters` untimed calls, then `workload.measured_iters`
+/// timed ones, returning the measured latencies in call order.
+pub async fn run_workload(client: &mut BenchClient, workload: &Workload) -> anyhow::Result<Vec<Duration>> {
+    for _ in 0..workload.warmup_iters {
+        client.call(&workload.command, &workload.args).await?;
+    }
+
+    let mut latencies = Vec::with_capacity(workload.measured_iters as usize);
+    for _ in 0..workload.measured_iters {
+        latencies.push(client.call(&workload.command, &workload.args).await?);
+    }
+    Ok(latencies)
+}
+
+/// Spawn the backend the same way `start_guru_service` does (`node
+/// scripts/guru-backend-runner.cjs` from the desktop package directory),
+/// then connect to it once its WebSocket gateway accepts connections.
+pub async fn spawn_and_connect() -> anyhow::Result<BenchClient> {
+    let mut cmd = Command::new("node");
+    cmd.arg("scripts/guru-backend-runner.cjs");
+    cmd.current_dir("packages/desktop");
+
+    let backend_process = cmd
+        .spawn()
+        .map_err(|e| anyhow::anyhow!("failed to spawn backend: {e}"))?;
+
+    let deadline = Instant::now() + CONNECT_TIMEOUT;
+    let ws = loop {
+        match connect_async(format!("ws://{BACKEND_ADDR}")).await {
+            Ok((ws, _response)) => break ws,
+            Err(e) if Instant::now() < deadline => {
+                log::debug!("waiting for backend to accept connections: {e}");
+                tokio::time::sleep(CONNECT_RETRY_DELAY).await;
+            }
+            Err(e) => anyhow::bail!("backend never accepted a connection at {BACKEND_ADDR}: {e}"),
+        }
+    };
+
+    Ok(BenchClient {
+        backend_process,
+        ws,
+        next_id: 1,
+    })
+}
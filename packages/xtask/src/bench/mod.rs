@@ -0,0 +1,88 @@
+//! `cargo xtask bench` — runs one or more analysis workloads against a
+//! freshly spawned backend and reports latency percentiles, for tracking
+//! analysis performance regressions across commits.
+
+mod backend;
+mod report;
+mod workload;
+
+use std::path::PathBuf;
+
+use workload::Workload;
+
+struct BenchArgs {
+    workload_paths: Vec<PathBuf>,
+    reports_dir: PathBuf,
+    dashboard_url: Option<String>,
+}
+
+fn parse_args(args: Vec<String>) -> anyhow::Result<BenchArgs> {
+    let mut workload_paths = Vec::new();
+    let mut reports_dir = PathBuf::from("reports");
+    let mut dashboard_url = None;
+
+    let mut iter = args.into_iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--reports-dir" => {
+                reports_dir = iter
+                    .next()
+                    .ok_or_else(|| anyhow::anyhow!("--reports-dir needs a path"))?
+                    .into();
+            }
+            "--dashboard-url" => {
+                dashboard_url = Some(
+                    iter.next()
+                        .ok_or_else(|| anyhow::anyhow!("--dashboard-url needs a URL"))?,
+                );
+            }
+            path => workload_paths.push(PathBuf::from(path)),
+        }
+    }
+
+    if workload_paths.is_empty() {
+        anyhow::bail!(
+            "usage: cargo xtask bench <workload.json>... [--reports-dir DIR] [--dashboard-url URL]"
+        );
+    }
+
+    Ok(BenchArgs {
+        workload_paths,
+        reports_dir,
+        dashboard_url,
+    })
+}
+
+/// Run every workload named on the command line, in order, against one
+/// backend process spawned for the whole batch.
+pub async fn run(args: Vec<String>) -> anyhow::Result<()> {
+    let args = parse_args(args)?;
+    std::fs::create_dir_all(&args.reports_dir)?;
+
+    let mut client = backend::spawn_and_connect().await?;
+
+    for path in &args.workload_paths {
+        let workload = Workload::load(path)?;
+        log::info!("running workload `{}` from {}", workload.name, path.display());
+
+        let measurement = backend::run_workload(&mut client, &workload).await?;
+        let report = report::Report::build(&workload, measurement)?;
+
+        let report_path = report.write_to_dir(&args.reports_dir)?;
+        log::info!(
+            "`{}`: p50={:.1}ms p90={:.1}ms p99={:.1}ms (report: {})",
+            workload.name,
+            report.p50_ms,
+            report.p90_ms,
+            report.p99_ms,
+            report_path.display()
+        );
+
+        if let Some(url) = &args.dashboard_url {
+            report.post_to(url).await?;
+        }
+    }
+
+    client.shutdown().await;
+    Ok(())
+}
@@ -0,0 +1,142 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::Serialize;
+
+use super::workload::Workload;
+
+/// OS/CPU/commit context captured alongside timings so a regression can be
+/// traced back to what it ran on, not just when
+#[derive(Debug, Serialize)]
+pub struct Environment {
+    pub os: &'static str,
+    pub arch: &'static str,
+    pub cpu_count: usize,
+    pub commit_hash: String,
+}
+
+impl Environment {
+    fn capture() -> Self {
+        Self {
+            os: std::env::consts::OS,
+            arch: std::env::consts::ARCH,
+            cpu_count: std::thread::available_parallelism()
+                .map(|n| n.get())
+                .unwrap_or(1),
+            commit_hash: git_commit_hash(),
+        }
+    }
+}
+
+fn git_commit_hash() -> String {
+    Command::new("git")
+        .args(["rev-parse", "HEAD"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .map(|hash| hash.trim().to_string())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+/// A completed workload run: its timings, percentiles, and the environment
+/// it ran in, as written to the reports folder (and optionally POSTed to a
+/// dashboard).
+#[derive(Debug, Serialize)]
+pub struct Report {
+    pub workload_name: String,
+    pub command: String,
+    pub warmup_iters: u32,
+    pub measured_iters: u32,
+    pub started_at_unix_secs: u64,
+    pub environment: Environment,
+    pub latencies_ms: Vec<f64>,
+    pub min_ms: f64,
+    pub mean_ms: f64,
+    pub p50_ms: f64,
+    pub p90_ms: f64,
+    pub p99_ms: f64,
+    pub max_ms: f64,
+}
+
+/// Nearest-rank percentile over `sorted` (already ascending), `p` in `0.0..=1.0`
+fn percentile(sorted: &[f64], p: f64) -> f64 {
+    if sorted.is_empty() {
+        return 0.0;
+    }
+    let rank = ((sorted.len() - 1) as f64 * p).round() as usize;
+    sorted[rank]
+}
+
+impl Report {
+    pub fn build(workload: &Workload, measurement: Vec<Duration>) -> anyhow::Result<Self> {
+        let mut latencies_ms: Vec<f64> = measurement.iter().map(Duration::as_secs_f64).map(|s| s * 1000.0).collect();
+        latencies_ms.sort_by(|a, b| a.total_cmp(b));
+
+        let mean_ms = latencies_ms.iter().sum::<f64>() / latencies_ms.len() as f64;
+
+        Ok(Self {
+            workload_name: workload.name.clone(),
+            command: workload.command.clone(),
+            warmup_iters: workload.warmup_iters,
+            measured_iters: workload.measured_iters,
+            started_at_unix_secs: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+            environment: Environment::capture(),
+            min_ms: *latencies_ms.first().unwrap_or(&0.0),
+            max_ms: *latencies_ms.last().unwrap_or(&0.0),
+            p50_ms: percentile(&latencies_ms, 0.50),
+            p90_ms: percentile(&latencies_ms, 0.90),
+            p99_ms: percentile(&latencies_ms, 0.99),
+            mean_ms,
+            latencies_ms,
+        })
+    }
+
+    pub fn write_to_dir(&self, dir: &Path) -> anyhow::Result<PathBuf> {
+        let path = dir.join(format!(
+            "{}-{}.json",
+            self.workload_name, self.started_at_unix_secs
+        ));
+        std::fs::write(&path, serde_json::to_string_pretty(self)?)?;
+        Ok(path)
+    }
+
+    pub async fn post_to(&self, dashboard_url: &str) -> anyhow::Result<()> {
+        let response = reqwest::Client::new()
+            .post(dashboard_url)
+            .json(self)
+            .send()
+            .await
+            .map_err(|e| anyhow::anyhow!("failed to POST report to {dashboard_url}: {e}"))?;
+
+        if !response.status().is_success() {
+            anyhow::bail!(
+                "dashboard at {dashboard_url} rejected report: HTTP {}",
+                response.status()
+            );
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn percentile_picks_nearest_rank() {
+        let sorted = vec![10.0, 20.0, 30.0, 40.0, 50.0];
+        assert_eq!(percentile(&sorted, 0.0), 10.0);
+        assert_eq!(percentile(&sorted, 0.5), 30.0);
+        assert_eq!(percentile(&sorted, 1.0), 50.0);
+    }
+
+    #[test]
+    fn percentile_of_empty_series_is_zero() {
+        assert_eq!(percentile(&[], 0.9), 0.0);
+    }
+}
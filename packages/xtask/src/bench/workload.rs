@@ -0,0 +1,79 @@
+use std::path::Path;
+
+use serde::Deserialize;
+use serde_json::Value;
+
+fn default_warmup_iters() -> u32 {
+    3
+}
+
+fn default_measured_iters() -> u32 {
+    20
+}
+
+/// A named, reproducible analysis scenario to benchmark: which backend
+/// command to invoke, its argument payload, and how many warmup/measured
+/// iterations to run. Loaded from a workload JSON file passed on the
+/// `cargo xtask bench` command line.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Workload {
+    pub name: String,
+    /// Guru backend command, e.g. `analyzeFilesystem`, `analyzeFilesManual`,
+    /// `queryKnowledgeBase` — forwarded verbatim to `send_guru_command`
+    pub command: String,
+    #[serde(default)]
+    pub args: Vec<Value>,
+    /// Iterations run and discarded before timing starts, to let the
+    /// backend warm up caches/JIT
+    #[serde(default = "default_warmup_iters")]
+    pub warmup_iters: u32,
+    /// Iterations whose latency is recorded
+    #[serde(default = "default_measured_iters")]
+    pub measured_iters: u32,
+}
+
+impl Workload {
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("failed to read workload {}: {e}", path.display()))?;
+        let workload: Workload = serde_json::from_str(&text)
+            .map_err(|e| anyhow::anyhow!("failed to parse workload {}: {e}", path.display()))?;
+
+        if workload.measured_iters == 0 {
+            anyhow::bail!("workload `{}` has measured_iters = 0", workload.name);
+        }
+
+        Ok(workload)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn defaults_apply_when_fields_omitted() {
+        let workload: Workload = serde_json::from_str(
+            r#"{"name": "analyze-small-repo", "command": "analyzeFilesystem", "args": [{}]}"#,
+        )
+        .unwrap();
+
+        assert_eq!(workload.warmup_iters, default_warmup_iters());
+        assert_eq!(workload.measured_iters, default_measured_iters());
+    }
+
+    #[test]
+    fn rejects_zero_measured_iterations() {
+        let dir = std::env::temp_dir().join("guru-xtask-bench-test-zero-iters.json");
+        std::fs::write(
+            &dir,
+            r#"{"name": "x", "command": "analyzeFilesystem", "measured_iters": 0}"#,
+        )
+        .unwrap();
+
+        let err = Workload::load(&dir).unwrap_err();
+        assert!(err.to_string().contains("measured_iters"));
+
+        std::fs::remove_file(&dir).unwrap();
+    }
+}
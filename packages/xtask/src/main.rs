@@ -0,0 +1,18 @@
+//! Maintainer-only task runner for the Guru workspace, invoked as
+//! `cargo xtask <task>`. Not built or shipped as part of the desktop app.
+
+mod bench;
+
+use std::env;
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    env_logger::init();
+
+    let mut args = env::args().skip(1);
+    match args.next().as_deref() {
+        Some("bench") => bench::run(args.collect()).await,
+        Some(other) => anyhow::bail!("unknown xtask `{other}` (known tasks: bench)"),
+        None => anyhow::bail!("usage: cargo xtask <task>\n\nknown tasks: bench"),
+    }
+}